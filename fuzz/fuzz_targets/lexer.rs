@@ -0,0 +1,13 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate interpreter;
+
+use interpreter::lexer::Lexer;
+use interpreter::token::Token;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let mut lexer = Lexer::new(input);
+        while lexer.next_token() != Token::EndOfFile {}
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate interpreter;
+
+use interpreter::lexer::Lexer;
+use interpreter::parser::Parser;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let mut parser = Parser::new(Lexer::new(input));
+        if let Ok(program) = parser.parse_program() {
+            let _ = program.to_string();
+        }
+    }
+});
@@ -2,34 +2,89 @@
 #![allow(dead_code)]
 
 use std::fmt;
+use std::rc::Rc;
+use num_bigint::BigInt;
 use token::Token;
+use token::Span;
 use self::Statement::*;
 use self::Expression::*;
 
+/// Code that recurses over the AST moves onto a new stack segment of
+/// `STACK_SEGMENT` bytes whenever less than `RED_ZONE` is left, so it runs on
+/// any thread, however small its stack.
+pub const RED_ZONE: usize = 256 * 1024;
+pub const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     LetStatement {
         name: String,
         value: Expression,
+        span: Span,
     },
     ReturnStatement {
-        value: Expression
+        value: Expression,
+        span: Span,
     },
     ExpressionStatement {
         expression: Expression,
+        span: Span,
     },
     BlockStatement {
-        statements: Vec<Statement>
+        statements: Vec<Statement>,
+        span: Span,
+    },
+    /// `target = value`, or with an `operator` the compound `target op= value`.
+    /// The target is a name or an index expression on a name.
+    AssignStatement {
+        target: Expression,
+        operator: Option<Token>,
+        value: Expression,
+        span: Span,
+    },
+    WhileStatement {
+        condition: Expression,
+        body: Box<Statement>,
+        span: Span,
     },
+    ForStatement {
+        variable: String,
+        iterable: Expression,
+        body: Box<Statement>,
+        span: Span,
+    },
+    BreakStatement {
+        span: Span,
+    },
+    ContinueStatement {
+        span: Span,
+    },
+}
+
+impl Statement {
+
+    pub fn span(&self) -> Span {
+        match *self {
+            LetStatement{ span, .. } => span,
+            ReturnStatement{ span, .. } => span,
+            ExpressionStatement{ span, .. } => span,
+            BlockStatement{ span, .. } => span,
+            AssignStatement{ span, .. } => span,
+            WhileStatement{ span, .. } => span,
+            ForStatement{ span, .. } => span,
+            BreakStatement{ span } => span,
+            ContinueStatement{ span } => span,
+        }
+    }
 }
 
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            LetStatement{ ref name, ref value } => write!(f, "let {} = {}", name, value.to_string()),
-            ReturnStatement{ ref value } => write!(f, "return {}", value.to_string()),
-            ExpressionStatement{ ref expression } => write!(f, "{}", expression.to_string()),
-            BlockStatement{ ref statements } => {
+        stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || match *self {
+            LetStatement{ ref name, ref value, .. } => write!(f, "let {} = {}", name, value),
+            ReturnStatement{ ref value, .. } => write!(f, "return {}", value),
+            ExpressionStatement{ ref expression, .. } => write!(f, "{}", expression),
+            BlockStatement{ ref statements, .. } => {
                 let mut stmts = String::new();
                 for s in statements {
                     stmts.push_str(&s.to_string());
@@ -37,64 +92,138 @@ impl fmt::Display for Statement {
                 }
                 write!(f, "{{ {} }}", stmts)
             },
-        }
+            AssignStatement{ ref target, operator: Some(ref operator), ref value, .. } => write!(f, "{} {}= {}", target, operator, value),
+            AssignStatement{ ref target, operator: None, ref value, .. } => write!(f, "{} = {}", target, value),
+            WhileStatement{ ref condition, ref body, .. } => write!(f, "while ({}) {}", condition, body),
+            ForStatement{ ref variable, ref iterable, ref body, .. } => write!(f, "for ({} in {}) {}", variable, iterable, body),
+            BreakStatement{ .. } => write!(f, "break"),
+            ContinueStatement{ .. } => write!(f, "continue"),
+        })
 
     }
 }
 
+impl Expression {
+
+    pub fn span(&self) -> Span {
+        match *self {
+            IdentifierExpression{ span, .. } => span,
+            IntegerExpression{ span, .. } => span,
+            FloatExpression{ span, .. } => span,
+            StringExpression{ span, .. } => span,
+            BooleanExpression{ span, .. } => span,
+            PrefixExpression{ span, .. } => span,
+            InfixExpression{ span, .. } => span,
+            IfExpression{ span, .. } => span,
+            FunctionExpression{ span, .. } => span,
+            CallExpression{ span, .. } => span,
+            ArrayExpression{ span, .. } => span,
+            IndexExpression{ span, .. } => span,
+            HashExpression{ span, .. } => span,
+        }
+    }
+}
+
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            IdentifierExpression{ ref value } => write!(f, "{}", value),
-            IntegerExpression{ value } => write!(f, "{}", value),
-            BooleanExpression{ value } => write!(f, "{}", value),
-            PrefixExpression{ ref prefix, ref right } => write!(f, "({}{})", prefix, right.to_string()),
-            InfixExpression{ ref left, ref operator, ref right } => write!(f, "({} {} {})", left.to_string(), operator.to_string(), right.to_string()),
-            IfExpression { ref condition, ref consequence, ref alternative } => write!(f, "(if {} {{ {} }} else {{ {:?} }})", condition.to_string(), consequence.to_string(), alternative),
-            FunctionExpression { ref parameters, ref body } => write!(f, "fn({}) {}", parameters.join(", "), body.to_string()),
-            CallExpression { ref name, ref arguments } => {
+        stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || match *self {
+            IdentifierExpression{ ref value, .. } => write!(f, "{}", value),
+            IntegerExpression{ ref value, .. } => write!(f, "{}", value),
+            FloatExpression{ value, .. } => write!(f, "{:?}", value),
+            StringExpression{ ref value, .. } => write!(f, "{:?}", value),
+            BooleanExpression{ value, .. } => write!(f, "{}", value),
+            PrefixExpression{ ref prefix, ref right, .. } => write!(f, "({}{})", prefix, right),
+            InfixExpression{ ref left, ref operator, ref right, .. } => write!(f, "({} {} {})", left, operator, right),
+            IfExpression { ref condition, ref consequence, ref alternative, .. } => {
+                write!(f, "if ({}) {}", condition, consequence)?;
+                match *alternative {
+                    Some(ref alternative) => write!(f, " else {}", alternative),
+                    None => Ok(()),
+                }
+            },
+            FunctionExpression { ref parameters, ref body, .. } => write!(f, "fn({}) {}", parameters.join(", "), body),
+            CallExpression { ref function, ref arguments, .. } => {
                 let mut exprs = Vec::new();
                 for a in arguments {
                     exprs.push(a.to_string());
                 }
-                write!(f, "{}({})", name, exprs.join(", "))
+                write!(f, "{}({})", function, exprs.join(", "))
             },
-        }
+            ArrayExpression { ref elements, .. } => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            },
+            IndexExpression { ref left, ref index, .. } => write!(f, "({}[{}])", left, index),
+            HashExpression { ref pairs, .. } => {
+                let pairs: Vec<String> = pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            },
+        })
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     IdentifierExpression {
-        value: String
+        value: String,
+        span: Span,
     },
     IntegerExpression {
-        value: u32
+        value: BigInt,
+        span: Span,
+    },
+    FloatExpression {
+        value: f64,
+        span: Span,
+    },
+    StringExpression {
+        value: String,
+        span: Span,
     },
     BooleanExpression {
-        value: bool
+        value: bool,
+        span: Span,
     },
     PrefixExpression {
         prefix: Token,
-        right: Box<Expression>
+        right: Box<Expression>,
+        span: Span,
     },
     InfixExpression {
         left: Box<Expression>,
         operator: Token,
-        right: Box<Expression>
+        right: Box<Expression>,
+        span: Span,
     },
     IfExpression {
         condition: Box<Expression>,
         consequence: Box<Statement>,
-        alternative: Option<Box<Statement>>
+        /// A block, or for `else if` the statement of the nested `if`.
+        alternative: Option<Box<Statement>>,
+        span: Span,
     },
     FunctionExpression {
         parameters: Vec<String>,
-        body: Box<Statement>
+        body: Rc<Statement>,
+        span: Span,
     },
     CallExpression {
-        name: String,
-        arguments: Vec<Expression>
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
+    ArrayExpression {
+        elements: Vec<Expression>,
+        span: Span,
+    },
+    IndexExpression {
+        left: Box<Expression>,
+        index: Box<Expression>,
+        span: Span,
+    },
+    HashExpression {
+        pairs: Vec<(Expression, Expression)>,
+        span: Span,
     },
 }
 
@@ -109,16 +238,15 @@ impl Program {
         Program { statements: stmts }
     }
 
-    // TODO for testing purpose only
-    pub fn statements(&self) -> Vec<Statement> {
-        self.statements.clone()
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
     }
 }
 
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut stmts = String::new();
-        let ref statements = self.statements;
+        let statements = &self.statements;
         for s in statements {
             stmts.push_str(&s.to_string());
             // stmts.push_str("; ");
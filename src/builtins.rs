@@ -0,0 +1,232 @@
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+use num_traits::FromPrimitive;
+use num_traits::ToPrimitive;
+
+use object::Object;
+use object::Object::*;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Arity {
+    Exactly(usize),
+    Between(usize, usize),
+    Any,
+}
+
+impl Arity {
+
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exactly(n) => count == n,
+            Arity::Between(min, max) => min <= count && count <= max,
+            Arity::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Arity::Exactly(n) => write!(f, "{}", n),
+            Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+            Arity::Any => write!(f, "any number"),
+        }
+    }
+}
+
+/// A builtin's implementation. It is only called with an argument count
+/// matching its arity, and an error becomes a runtime error at the call.
+pub type BuiltinFunction = fn(&[Object]) -> Result<Object, String>;
+
+/// A function implemented in Rust that scripts call like any other.
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub function: BuiltinFunction,
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Builtin) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+/// Every builtin. Identifiers resolve here when no binding of the same name
+/// is in scope. None of them modify their arguments.
+pub static BUILTINS: &[Builtin] = &[
+    Builtin { name: "len", arity: Arity::Exactly(1), function: len },
+    Builtin { name: "first", arity: Arity::Exactly(1), function: first },
+    Builtin { name: "last", arity: Arity::Exactly(1), function: last },
+    Builtin { name: "rest", arity: Arity::Exactly(1), function: rest },
+    Builtin { name: "push", arity: Arity::Exactly(2), function: push },
+    Builtin { name: "puts", arity: Arity::Any, function: puts },
+    Builtin { name: "type", arity: Arity::Exactly(1), function: type_of },
+    Builtin { name: "str", arity: Arity::Exactly(1), function: str },
+    Builtin { name: "int", arity: Arity::Exactly(1), function: int },
+    Builtin { name: "float", arity: Arity::Exactly(1), function: float },
+    Builtin { name: "bool", arity: Arity::Exactly(1), function: bool },
+    Builtin { name: "range", arity: Arity::Between(1, 2), function: range },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+fn unsupported(name: &str, args: &[Object]) -> Result<Object, String> {
+    let got = args.first().map_or("nothing", Object::type_name);
+    Err(format!("argument to `{}` not supported, got {}", name, got))
+}
+
+fn len(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Str(ref value)] => Ok(Int(value.chars().count() as i64)),
+        [Array(ref elements)] => Ok(Int(elements.len() as i64)),
+        [Hash(ref pairs)] => Ok(Int(pairs.len() as i64)),
+        _ => unsupported("len", args),
+    }
+}
+
+fn first(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Array(ref elements)] => Ok(elements.first().cloned().unwrap_or(Null)),
+        _ => unsupported("first", args),
+    }
+}
+
+fn last(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Array(ref elements)] => Ok(elements.last().cloned().unwrap_or(Null)),
+        _ => unsupported("last", args),
+    }
+}
+
+fn rest(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Array(ref elements)] if elements.is_empty() => Ok(Null),
+        [Array(ref elements)] => Ok(Array(Rc::new(elements[1..].to_vec()))),
+        _ => unsupported("rest", args),
+    }
+}
+
+fn push(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Array(ref elements), ref value] => {
+            let mut elements = elements.to_vec();
+            elements.push(value.clone());
+            Ok(Array(Rc::new(elements)))
+        },
+        _ => unsupported("push", args),
+    }
+}
+
+/// Prints each argument on its own line. Failing to write, say to a closed
+/// pipe, is an error rather than a panic.
+fn puts(args: &[Object]) -> Result<Object, String> {
+    let mut stdout = io::stdout().lock();
+    for arg in args {
+        writeln!(stdout, "{}", arg).map_err(|error| format!("cannot write to stdout: {}", error))?;
+    }
+    Ok(Null)
+}
+
+fn type_of(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [ref value] => Ok(Str(value.type_name().to_string())),
+        _ => unsupported("type", args),
+    }
+}
+
+fn str(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [ref value] => Ok(Str(value.to_string())),
+        _ => unsupported("str", args),
+    }
+}
+
+fn int(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Int(value)] => Ok(Int(*value)),
+        [BigInt(ref value)] => Ok(BigInt(value.clone())),
+        [Bool(value)] => Ok(Int(i64::from(*value))),
+        // truncates toward zero
+        [Float(value)] => match BigInt::from_f64(*value) {
+            Some(value) => Ok(Object::from(value)),
+            None => Err(format!("cannot convert {:?} to an integer", value)),
+        },
+        [Str(ref value)] => match value.trim().parse::<BigInt>() {
+            Ok(value) => Ok(Object::from(value)),
+            Err(_) => Err(format!("cannot convert {:?} to an integer", value)),
+        },
+        _ => unsupported("int", args),
+    }
+}
+
+fn float(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Float(value)] => Ok(Float(*value)),
+        [Int(value)] => Ok(Float(*value as f64)),
+        [BigInt(ref value)] => Ok(Float(value.to_f64().unwrap_or(f64::NAN))),
+        [Str(ref value)] => match value.trim().parse() {
+            Ok(value) => Ok(Float(value)),
+            Err(_) => Err(format!("cannot convert {:?} to a float", value)),
+        },
+        _ => unsupported("float", args),
+    }
+}
+
+fn bool(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [ref value] => Ok(Bool(value.is_truthy())),
+        _ => unsupported("bool", args),
+    }
+}
+
+/// `range(end)` counts from zero, `range(start, end)` from `start`; either
+/// way `end` itself is excluded.
+fn range(args: &[Object]) -> Result<Object, String> {
+    match args {
+        [Int(end)] => Ok(Range { start: 0, end: *end }),
+        [Int(start), Int(end)] => Ok(Range { start: *start, end: *end }),
+        // names whichever argument is not an integer
+        [Int(_), ref other] | [ref other, ..] => unsupported("range", ::std::slice::from_ref(other)),
+        _ => unsupported("range", args),
+    }
+}
+
+#[test]
+fn lookup_test() {
+    assert_eq!(Some("len"), lookup("len").map(|b| b.name));
+    assert_eq!(None, lookup("nope"));
+    for (i, builtin) in BUILTINS.iter().enumerate() {
+        assert!(BUILTINS[..i].iter().all(|b| b.name != builtin.name), "{} is registered twice", builtin.name);
+    }
+}
+
+#[test]
+fn conversion_test() {
+    assert_eq!(Ok(Int(42)), int(&[Str(" 42 ".to_string())]));
+    assert_eq!(Ok(Int(1)), int(&[Bool(true)]));
+    assert_eq!(Ok(Int(-2)), int(&[Float(-2.9)]));
+    assert_eq!(Err("cannot convert inf to an integer".to_string()), int(&[Float(f64::INFINITY)]));
+    assert_eq!(Ok(BigInt(BigInt::from(10).pow(20))), int(&[Float(1e20)]));
+    assert_eq!(Ok(BigInt(BigInt::from(10).pow(20))), int(&[Str("100000000000000000000".to_string())]));
+    assert_eq!(Ok(Float(1e20)), float(&[BigInt(BigInt::from(10).pow(20))]));
+    assert_eq!(Ok(Float(2.0)), float(&[Int(2)]));
+    assert_eq!(Ok(Float(0.5)), float(&[Str("0.5".to_string())]));
+    assert_eq!(Err("cannot convert \"4x\" to an integer".to_string()), int(&[Str("4x".to_string())]));
+    assert_eq!(Ok(Str("[1, \"a\"]".to_string())), str(&[Array(Rc::new(vec![Int(1), Str("a".to_string())]))]));
+    assert_eq!(Ok(Str("a".to_string())), str(&[Str("a".to_string())]));
+    assert_eq!(Ok(Bool(false)), bool(&[Null]));
+    assert_eq!(Ok(Bool(true)), bool(&[Int(0)]));
+    assert_eq!(Ok(Str("hash".to_string())), type_of(&[Hash(Rc::default())]));
+}
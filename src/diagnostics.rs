@@ -0,0 +1,254 @@
+use std::fmt::Write;
+
+use evaluator::ErrorKind;
+use evaluator::RuntimeError;
+use evaluator::MAX_CALL_DEPTH;
+use evaluator::MAX_DEPTH;
+use parser::ParseError;
+use parser::ParseErrorKind;
+use parser::MAX_NESTING;
+use token::Position;
+use token::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A message about a span of source code, rendered the way rustc does: the
+/// location, the offending lines with the labelled spans underlined, and any
+/// notes below.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+
+    pub fn new(severity: Severity, message: &str, span: Span) -> Self {
+        let primary = Label { span, message: String::new(), primary: true };
+        Diagnostic { severity, message: message.to_string(), labels: vec![primary], notes: Vec::new() }
+    }
+
+    pub fn error(message: &str, span: Span) -> Self {
+        Diagnostic::new(Severity::Error, message, span)
+    }
+
+    /// Sets the text printed under the primary span.
+    pub fn with_label(mut self, message: &str) -> Self {
+        self.labels[0].message = message.to_string();
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label { span, message: message.to_string(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        self.labels[0].span
+    }
+
+    pub fn render(&self, file: &str, source: &str, color: bool) -> String {
+        let paint = |style: &'static str| if color { style } else { "" };
+        let reset = paint(RESET);
+        let gutter = paint(BLUE);
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|l| (l.span.start.line, !l.primary));
+        let width = labels.iter().map(|l| l.span.start.line.to_string().len()).max().unwrap_or(1);
+        let pad = " ".repeat(width);
+        let lines: Vec<&str> = source.lines().collect();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{}{}{}{}: {}{}", paint(self.severity.color()), self.severity.name(), reset, paint(BOLD), self.message, reset);
+        let _ = writeln!(out, "{}{}-->{} {}:{}", pad, gutter, reset, file, self.span().start);
+        let _ = writeln!(out, "{} {}|{}", pad, gutter, reset);
+
+        let mut previous_line = None;
+        for label in labels {
+            let start = label.span.start;
+            let text = lines.get(start.line - 1).cloned().unwrap_or("");
+            if previous_line != Some(start.line) {
+                let _ = writeln!(out, "{}{:>width$} |{} {}", gutter, start.line, reset, text, width = width);
+                previous_line = Some(start.line);
+            }
+            let (mark, style) = if label.primary { ('^', paint(self.severity.color())) } else { ('-', gutter) };
+            let underline = mark.to_string().repeat(underline_width(text, start, label.span.end));
+            let message = if label.message.is_empty() { String::new() } else { format!(" {}", label.message) };
+            let _ = writeln!(out, "{} {}|{} {}{}{}{}{}", pad, gutter, reset, indentation(text, start), style, underline, message, reset);
+        }
+
+        if !self.notes.is_empty() {
+            let _ = writeln!(out, "{} {}|{}", pad, gutter, reset);
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "{} {}={} {}note{}: {}", pad, gutter, reset, paint(BOLD), reset, note);
+        }
+        out
+    }
+
+    pub fn to_json(&self, file: &str) -> String {
+        let labels: Vec<String> = self.labels.iter().map(|l| {
+            format!("{{\"primary\":{},\"message\":{},\"start\":{},\"end\":{}}}", l.primary, json_string(&l.message), json_position(l.span.start), json_position(l.span.end))
+        }).collect();
+        let notes: Vec<String> = self.notes.iter().map(|n| json_string(n)).collect();
+        format!("{{\"severity\":{},\"message\":{},\"file\":{},\"labels\":[{}],\"notes\":[{}]}}",
+            json_string(self.severity.name()), json_string(&self.message), json_string(file), labels.join(","), notes.join(","))
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Diagnostic {
+        let diagnostic = Diagnostic::error(&error.to_string(), error.span);
+        match error.kind {
+            ParseErrorKind::Unexpected{ ref expected, .. } => diagnostic.with_label(&format!("expected {}", expected)),
+            ParseErrorKind::Lex(_) | ParseErrorKind::OutsideLoop(_) | ParseErrorKind::InvalidAssignmentTarget => diagnostic,
+            ParseErrorKind::TooDeeplyNested => diagnostic.with_note(&format!("expressions may nest at most {} levels deep", MAX_NESTING)),
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Diagnostic {
+        let diagnostic = Diagnostic::error(&error.message, error.span);
+        match error.kind {
+            ErrorKind::CallDepthExceeded => diagnostic.with_note(&format!("calls may nest at most {} levels deep", MAX_CALL_DEPTH)),
+            ErrorKind::DepthExceeded => diagnostic.with_note(&format!("calls and the code inside them may nest at most {} levels deep in all", MAX_DEPTH)),
+            _ => diagnostic,
+        }
+    }
+}
+
+/// The whitespace that lines a marker up under `start`, keeping tabs so the
+/// alignment survives any tab width.
+fn indentation(text: &str, start: Position) -> String {
+    text.chars().take(start.column - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect()
+}
+
+fn underline_width(text: &str, start: Position, end: Position) -> usize {
+    let width = if end.line == start.line {
+        end.column.saturating_sub(start.column)
+    } else {
+        (text.chars().count() + 1).saturating_sub(start.column)
+    };
+    width.max(1)
+}
+
+fn json_position(position: Position) -> String {
+    format!("{{\"offset\":{},\"line\":{},\"column\":{}}}", position.offset, position.line, position.column)
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+fn span(start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+    Span::new(Position { offset: start.0, line: start.1, column: start.2 }, Position { offset: end.0, line: end.1, column: end.2 })
+}
+
+#[test]
+fn render_test() {
+    let source = "let x = 1;\nlet y = x + true;\n";
+    let diagnostic = Diagnostic::error("type mismatch: integer + boolean", span((19, 2, 9), (27, 2, 17)))
+        .with_label("cannot add these")
+        .with_secondary(span((4, 1, 5), (5, 1, 6)), "defined here")
+        .with_note("only numbers can be added");
+    let expected = "\
+error: type mismatch: integer + boolean
+ --> main.mk:2:9
+  |
+1 | let x = 1;
+  |     - defined here
+2 | let y = x + true;
+  |         ^^^^^^^^ cannot add these
+  |
+  = note: only numbers can be added
+";
+    assert_eq!(expected, diagnostic.render("main.mk", source, false));
+}
+
+#[test]
+fn render_color_test() {
+    let diagnostic = Diagnostic::error("boom", span((0, 1, 1), (1, 1, 2)));
+    let rendered = diagnostic.render("<repl>", "x", true);
+    assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m\x1b[1m: boom\x1b[0m\n"));
+    assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+}
+
+#[test]
+fn render_end_of_input_test() {
+    use lexer::Lexer;
+    use parser::Parser;
+    let source = "add(1,\n\t2";
+    let errors = Parser::new(Lexer::new(source)).parse_program().unwrap_err();
+    let expected = "\
+error: expected `)`, found end of input
+ --> f.mk:2:3
+  |
+2 | \t2
+  | \t ^ expected `)`
+";
+    assert_eq!(expected, Diagnostic::from(&errors[0]).render("f.mk", source, false));
+}
+
+#[test]
+fn to_json_test() {
+    let diagnostic = Diagnostic::error("unexpected character `\"`", span((0, 1, 1), (1, 1, 2))).with_note("a\tb");
+    assert_eq!(
+        "{\"severity\":\"error\",\"message\":\"unexpected character `\\\"`\",\"file\":\"a\\\\b.mk\",\"labels\":[{\"primary\":true,\"message\":\"\",\"start\":{\"offset\":0,\"line\":1,\"column\":1},\"end\":{\"offset\":1,\"line\":1,\"column\":2}}],\"notes\":[\"a\\tb\"]}",
+        diagnostic.to_json("a\\b.mk"));
+}
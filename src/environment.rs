@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr;
+use std::rc::Rc;
+
+use object::Object;
+
+pub type Env = Rc<RefCell<Environment>>;
+
+#[derive(Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+}
+
+impl Environment {
+
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Environment::default()))
+    }
+
+    pub fn new_enclosed(outer: &Env) -> Env {
+        Rc::new(RefCell::new(Environment { store: HashMap::new(), outer: Some(Rc::clone(outer)) }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => match self.outer {
+                Some(ref outer) => outer.borrow().get(name),
+                None => None,
+            },
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    /// Rebinds `name` in the nearest environment that binds it, returning
+    /// whether one did.
+    pub fn assign(&mut self, name: &str, value: Object) -> bool {
+        self.update(name, |slot| *slot = value).is_some()
+    }
+
+    /// Hands the nearest binding of `name` to `f` to change in place,
+    /// returning what `f` returns, or `None` if no environment binds it.
+    pub fn update<T, F: FnOnce(&mut Object) -> T>(&mut self, name: &str, f: F) -> Option<T> {
+        if let Some(slot) = self.store.get_mut(name) {
+            return Some(f(slot))
+        }
+        match self.outer {
+            Some(ref outer) => outer.borrow_mut().update(name, f),
+            None => None,
+        }
+    }
+
+    /// Empties this environment, and the enclosing ones that only it refers
+    /// to, handing back the values they bound.
+    pub fn take_values(&mut self) -> Vec<Object> {
+        let mut values: Vec<Object> = self.store.drain().map(|(_, value)| value).collect();
+        let mut outer = self.outer.take();
+        while let Some(mut env) = outer {
+            outer = match Rc::get_mut(&mut env) {
+                Some(env) => {
+                    let env = env.get_mut();
+                    values.extend(env.store.drain().map(|(_, value)| value));
+                    env.outer.take()
+                },
+                None => None,
+            };
+        }
+        values
+    }
+
+    /// The bindings made directly in this environment, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<(String, Object)> = self.store.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+}
+
+// Closures capture the environment they are defined in, and that environment
+// usually holds the closure itself, so neither comparison nor formatting may
+// walk into the bindings.
+impl PartialEq for Environment {
+    fn eq(&self, other: &Environment) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<&String> = self.store.keys().collect();
+        names.sort();
+        write!(f, "Environment {{ names: {:?}, enclosed: {} }}", names, self.outer.is_some())
+    }
+}
+
+#[test]
+fn environment_test() {
+    let outer = Environment::new();
+    outer.borrow_mut().set("x", Object::Int(1));
+    let inner = Environment::new_enclosed(&outer);
+    inner.borrow_mut().set("y", Object::Int(2));
+    assert_eq!(Some(Object::Int(1)), inner.borrow().get("x"));
+    assert_eq!(Some(Object::Int(2)), inner.borrow().get("y"));
+    assert_eq!(None, outer.borrow().get("y"));
+
+    inner.borrow_mut().set("x", Object::Int(3));
+    assert_eq!(Some(Object::Int(3)), inner.borrow().get("x"));
+    assert_eq!(Some(Object::Int(1)), outer.borrow().get("x"));
+    assert_eq!(vec![("x".to_string(), Object::Int(3)), ("y".to_string(), Object::Int(2))], inner.borrow().bindings());
+
+    assert!(inner.borrow_mut().assign("y", Object::Int(4)));
+    assert!(Environment::new_enclosed(&inner).borrow_mut().assign("x", Object::Int(5)));
+    assert!(!inner.borrow_mut().assign("z", Object::Int(6)));
+    assert_eq!(Some(Object::Int(4)), inner.borrow().get("y"));
+    assert_eq!(Some(Object::Int(5)), inner.borrow().get("x"));
+    assert_eq!(Some(Object::Int(1)), outer.borrow().get("x"));
+    assert_eq!(None, inner.borrow().get("z"));
+
+    assert_eq!(Some(true), Environment::new_enclosed(&inner).borrow_mut().update("x", |x| { *x = Object::Int(7); true }));
+    assert_eq!(None, inner.borrow_mut().update("z", |_| ()));
+    assert_eq!(Some(Object::Int(7)), inner.borrow().get("x"));
+}
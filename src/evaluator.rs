@@ -1,23 +1,1004 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
 
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use num_traits::Zero;
+
+use environment::Env;
+use environment::Environment;
+use ast::Program;
+use ast::RED_ZONE;
+use ast::STACK_SEGMENT;
+use builtins;
+use builtins::Arity;
+use ast::Statement;
+use ast::Statement::*;
 use ast::Expression;
 use ast::Expression::*;
+use object::HashKey;
 use object::Object;
 use object::Object::*;
+use token::Token;
+use token::Span;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+    TypeMismatch,
+    UnknownOperator,
+    UnknownIdentifier,
+    NotCallable,
+    WrongArgumentCount,
+    DivisionByZero,
+    CallDepthExceeded,
+    DepthExceeded,
+    NotIndexable,
+    InvalidArgument,
+    UnhashableKey,
+    NotIterable,
+    IndexOutOfRange,
+    InvalidAssignmentTarget,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Span,
+}
+
+impl RuntimeError {
+
+    pub fn new(kind: ErrorKind, message: String, span: Span) -> Self {
+        RuntimeError { kind, message, span }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for RuntimeError {}
 
-fn eval(node: Expression) -> Object {
-    match node {
-        IntegerExpression{ value } => Int(value as i64),
-        IdentifierExpression { value } => Str(value),
-        BooleanExpression { value } => Bool(value),
-        _ => Null,
+pub type EvalResult = Result<Object, RuntimeError>;
+
+/// Recursion deeper than this is reported as an error.
+pub const MAX_CALL_DEPTH: usize = 512;
+
+/// How many statements and expressions may be under evaluation at once,
+/// counting those in every active call. This bounds the stack an evaluation
+/// uses, however its calls and nesting combine.
+pub const MAX_DEPTH: usize = 8192;
+
+thread_local! {
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn eval_program(program: &Program, env: &Env) -> EvalResult {
+    let mut result = Null;
+    for statement in program.statements() {
+        result = eval_statement(statement, env)?;
+        if let Return(_) = result {
+            return Ok(result.unwrap_return())
+        }
     }
+    Ok(result)
 }
 
+fn eval_statement(statement: &Statement, env: &Env) -> EvalResult {
+    nested(statement.span(), || match *statement {
+        LetStatement{ ref name, ref value, .. } => {
+            let value = eval(value, env)?;
+            if value.is_unwinding() {
+                return Ok(value)
+            }
+            env.borrow_mut().set(name, value);
+            Ok(Null)
+        },
+        ReturnStatement{ ref value, .. } => {
+            let value = eval(value, env)?;
+            match value {
+                _ if value.is_unwinding() => Ok(value),
+                _ => Ok(Return(Box::new(value))),
+            }
+        },
+        ExpressionStatement{ ref expression, .. } => eval(expression, env),
+        BlockStatement{ ref statements, .. } => eval_block(statements, env),
+        AssignStatement{ ref target, ref operator, ref value, span } => eval_assign(target, operator, value, env, span),
+        WhileStatement{ ref condition, ref body, .. } => eval_while(condition, body, env),
+        ForStatement{ ref variable, ref iterable, ref body, .. } => eval_for(variable, iterable, body, env),
+        BreakStatement{ .. } => Ok(Break),
+        ContinueStatement{ .. } => Ok(Continue),
+    })
+}
+
+/// Runs `f` one level deeper into the program, failing once `MAX_DEPTH`
+/// levels are in progress.
+fn nested<F: FnOnce() -> EvalResult>(span: Span, f: F) -> EvalResult {
+    let depth = DEPTH.with(|d| d.get());
+    if depth >= MAX_DEPTH {
+        return Err(RuntimeError::new(ErrorKind::DepthExceeded, format!("maximum evaluation depth of {} exceeded", MAX_DEPTH), span))
+    }
+    DEPTH.with(|d| d.set(depth + 1));
+    let result = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, f);
+    DEPTH.with(|d| d.set(depth));
+    result
+}
+
+fn eval_block(statements: &[Statement], env: &Env) -> EvalResult {
+    let mut result = Null;
+    for statement in statements {
+        result = eval_statement(statement, env)?;
+        if result.is_unwinding() {
+            return Ok(result)
+        }
+    }
+    Ok(result)
+}
+
+/// Assigns to a name bound in this or an enclosing scope, or to an element
+/// of the array or hash it holds. Containers are values, so the element is
+/// changed in place only while the binding holds the sole copy.
+fn eval_assign(target: &Expression, operator: &Option<Token>, value: &Expression, env: &Env, span: Span) -> EvalResult {
+    let mut indices = Vec::new();
+    let mut root = target;
+    while let IndexExpression{ ref left, ref index, .. } = *root {
+        indices.push(&**index);
+        root = left;
+    }
+    let name = match *root {
+        IdentifierExpression{ ref value, .. } => value,
+        // the parser rejects these, but an AST can also be built by hand
+        _ => return Err(RuntimeError::new(ErrorKind::InvalidAssignmentTarget, "invalid assignment target".to_string(), target.span())),
+    };
+
+    let mut keys = Vec::with_capacity(indices.len());
+    for index in indices.into_iter().rev() {
+        let key = eval(index, env)?;
+        if key.is_unwinding() {
+            return Ok(key)
+        }
+        keys.push(key);
+    }
+    let value = eval(value, env)?;
+    if value.is_unwinding() {
+        return Ok(value)
+    }
+
+    let assigned = env.borrow_mut().update(name, |slot| assign_element(slot, &keys, operator, value, span));
+    match assigned {
+        Some(assigned) => assigned.map(|_| Null),
+        None => Err(RuntimeError::new(ErrorKind::UnknownIdentifier, format!("identifier not found: {}", name), root.span())),
+    }
+}
+
+/// Replaces the element of `slot` found by following `keys`, or with no keys
+/// left the value of `slot` itself. On error `slot` keeps its value.
+fn assign_element(slot: &mut Object, keys: &[Object], operator: &Option<Token>, value: Object, span: Span) -> Result<(), RuntimeError> {
+    let (key, keys) = match keys.split_first() {
+        Some(split) => split,
+        None => {
+            *slot = match *operator {
+                Some(ref operator) => eval_infix(operator, slot.clone(), value, span)?,
+                None => value,
+            };
+            return Ok(())
+        },
+    };
+    match (slot, key) {
+        (&mut Array(ref mut elements), &Int(i)) if usize::try_from(i).is_ok_and(|i| i < elements.len()) => {
+            assign_element(&mut Rc::make_mut(elements)[i as usize], keys, operator, value, span)
+        },
+        (&mut Array(ref elements), &Int(_)) | (&mut Array(ref elements), &BigInt(_)) => {
+            Err(RuntimeError::new(ErrorKind::IndexOutOfRange, format!("index {} out of range for array of length {}", key, elements.len()), span))
+        },
+        (&mut Hash(ref mut pairs), key) => {
+            let key = hash_key(key, span)?;
+            let pairs = Rc::make_mut(pairs);
+            if let Some(element) = pairs.get_mut(&key) {
+                return assign_element(element, keys, operator, value, span)
+            }
+            let mut element = Null;
+            assign_element(&mut element, keys, operator, value, span)?;
+            pairs.insert(key, element);
+            Ok(())
+        },
+        (container, key) => Err(RuntimeError::new(ErrorKind::NotIndexable, format!("index operator not supported: {}[{}]", container.type_name(), key.type_name()), span)),
+    }
+}
+
+fn eval_while(condition: &Expression, body: &Statement, env: &Env) -> EvalResult {
+    loop {
+        let value = eval(condition, env)?;
+        if value.is_unwinding() {
+            return Ok(value)
+        }
+        if !value.is_truthy() {
+            return Ok(Null)
+        }
+        match eval_statement(body, env)? {
+            Break => return Ok(Null),
+            result @ Return(_) => return Ok(result),
+            _ => {},
+        }
+    }
+}
+
+/// Runs `body` with `variable` bound, in the enclosing scope, to each element
+/// of an array, each key of a hash in order, or each integer of a range.
+fn eval_for(variable: &str, iterable: &Expression, body: &Statement, env: &Env) -> EvalResult {
+    let span = iterable.span();
+    let values: Box<dyn Iterator<Item = Object>> = match eval(iterable, env)? {
+        Array(ref elements) => {
+            let elements = Rc::clone(elements);
+            Box::new((0..elements.len()).map(move |i| elements[i].clone()))
+        },
+        Hash(ref pairs) => Box::new(pairs.keys().cloned().map(Object::from).collect::<Vec<_>>().into_iter()),
+        Range{ start, end } => Box::new((start..end).map(Int)),
+        value if value.is_unwinding() => return Ok(value),
+        value => return Err(RuntimeError::new(ErrorKind::NotIterable, format!("cannot iterate over {}", value.type_name()), span)),
+    };
+    for value in values {
+        env.borrow_mut().set(variable, value);
+        match eval_statement(body, env)? {
+            Break => break,
+            result @ Return(_) => return Ok(result),
+            _ => {},
+        }
+    }
+    Ok(Null)
+}
+
+fn eval(node: &Expression, env: &Env) -> EvalResult {
+    nested(node.span(), || match *node {
+        IntegerExpression{ ref value, .. } => Ok(Object::from(value.clone())),
+        FloatExpression{ value, .. } => Ok(Float(value)),
+        StringExpression{ ref value, .. } => Ok(Str(value.clone())),
+        IdentifierExpression { ref value, span } => lookup(value, env, span),
+        BooleanExpression { value, .. } => Ok(Bool(value)),
+        PrefixExpression { ref prefix, ref right, span } => eval_prefix_expression(prefix, right, env, span),
+        InfixExpression { ref left, ref operator, ref right, span } => eval_infix_expression(operator, left, right, env, span),
+        IfExpression { ref condition, ref consequence, ref alternative, .. } => eval_if_expression(condition, consequence, alternative, env),
+        FunctionExpression { ref parameters, ref body, .. } => Ok(Function { parameters: parameters.clone(), body: Rc::clone(body), env: Rc::clone(env) }),
+        CallExpression { ref function, ref arguments, span } => eval_call_expression(function, arguments, env, span),
+        ArrayExpression { ref elements, .. } => eval_array(elements, env),
+        IndexExpression { ref left, ref index, span } => eval_index_expression(left, index, env, span),
+        HashExpression { ref pairs, .. } => eval_hash(pairs, env),
+    })
+}
+
+fn eval_prefix_expression(prefix: &Token, right: &Expression, env: &Env, span: Span) -> EvalResult {
+    let right = eval(right, env)?;
+    if right.is_unwinding() {
+        return Ok(right)
+    }
+    eval_prefix(prefix, right, span)
+}
+
+fn eval_infix_expression(operator: &Token, left: &Expression, right: &Expression, env: &Env, span: Span) -> EvalResult {
+    let left = eval(left, env)?;
+    if left.is_unwinding() {
+        return Ok(left)
+    }
+    if let Token::And | Token::Or = *operator {
+        return eval_logical(operator, left, right, env)
+    }
+    let right = eval(right, env)?;
+    if right.is_unwinding() {
+        return Ok(right)
+    }
+    eval_infix(operator, left, right, span)
+}
+
+/// `&&` and `||` work on the truthiness of any operands and give a boolean.
+/// The right operand is only evaluated when the left does not decide it.
+fn eval_logical(operator: &Token, left: Object, right: &Expression, env: &Env) -> EvalResult {
+    let decided = match *operator {
+        Token::And => !left.is_truthy(),
+        _ => left.is_truthy(),
+    };
+    if decided {
+        return Ok(Bool(left.is_truthy()))
+    }
+    let right = eval(right, env)?;
+    if right.is_unwinding() {
+        return Ok(right)
+    }
+    Ok(Bool(right.is_truthy()))
+}
+
+fn eval_if_expression(condition: &Expression, consequence: &Statement, alternative: &Option<Box<Statement>>, env: &Env) -> EvalResult {
+    let condition = eval(condition, env)?;
+    if condition.is_unwinding() {
+        return Ok(condition)
+    }
+    if condition.is_truthy() {
+        eval_statement(consequence, env)
+    } else if let Some(ref alternative) = *alternative {
+        eval_statement(alternative, env)
+    } else {
+        Ok(Null)
+    }
+}
+
+fn eval_call_expression(function: &Expression, arguments: &[Expression], env: &Env, span: Span) -> EvalResult {
+    let function = eval(function, env)?;
+    if function.is_unwinding() {
+        return Ok(function)
+    }
+    let args = match eval_array(arguments, env)? {
+        Array(ref mut args) => mem::take(Rc::make_mut(args)),
+        returned => return Ok(returned),
+    };
+    apply(function, args, span)
+}
+
+/// Evaluates `elements` in order into an array, or into the first element
+/// that unwinds.
+fn eval_array(elements: &[Expression], env: &Env) -> EvalResult {
+    let mut values = Vec::with_capacity(elements.len());
+    for element in elements {
+        let value = eval(element, env)?;
+        if value.is_unwinding() {
+            return Ok(value)
+        }
+        values.push(value);
+    }
+    Ok(Array(Rc::new(values)))
+}
+
+fn eval_hash(pairs: &[(Expression, Expression)], env: &Env) -> EvalResult {
+    let mut hash = BTreeMap::new();
+    for (key, value) in pairs {
+        let span = key.span();
+        let key = eval(key, env)?;
+        if key.is_unwinding() {
+            return Ok(key)
+        }
+        let key = hash_key(&key, span)?;
+        let value = eval(value, env)?;
+        if value.is_unwinding() {
+            return Ok(value)
+        }
+        hash.insert(key, value);
+    }
+    Ok(Hash(Rc::new(hash)))
+}
+
+fn hash_key(key: &Object, span: Span) -> Result<HashKey, RuntimeError> {
+    match HashKey::from_object(key) {
+        Some(key) => Ok(key),
+        None => Err(RuntimeError::new(ErrorKind::UnhashableKey, format!("unusable as hash key: {}", key.type_name()), span)),
+    }
+}
+
+fn eval_index_expression(left: &Expression, index: &Expression, env: &Env, span: Span) -> EvalResult {
+    let left = eval(left, env)?;
+    if left.is_unwinding() {
+        return Ok(left)
+    }
+    let index = eval(index, env)?;
+    if index.is_unwinding() {
+        return Ok(index)
+    }
+    eval_index(left, index, span)
+}
+
+/// Resolves `name` to its binding, falling back to the builtin of that name.
+fn lookup(name: &str, env: &Env, span: Span) -> EvalResult {
+    if let Some(value) = env.borrow().get(name) {
+        return Ok(value)
+    }
+    match builtins::lookup(name) {
+        Some(builtin) => Ok(Builtin(builtin)),
+        None => Err(RuntimeError::new(ErrorKind::UnknownIdentifier, format!("identifier not found: {}", name), span)),
+    }
+}
+
+fn wrong_argument_count(expected: Arity, got: usize, span: Span) -> RuntimeError {
+    RuntimeError::new(ErrorKind::WrongArgumentCount, format!("wrong number of arguments: expected {}, got {}", expected, got), span)
+}
+
+fn apply(function: Object, args: Vec<Object>, span: Span) -> EvalResult {
+    match function {
+        Function { ref parameters, ref body, ref env } => {
+            if parameters.len() != args.len() {
+                return Err(wrong_argument_count(Arity::Exactly(parameters.len()), args.len(), span))
+            }
+            let depth = CALL_DEPTH.with(|d| d.get());
+            if depth >= MAX_CALL_DEPTH {
+                return Err(RuntimeError::new(ErrorKind::CallDepthExceeded, format!("maximum call depth of {} exceeded", MAX_CALL_DEPTH), span))
+            }
+            let scope = Environment::new_enclosed(env);
+            for (parameter, arg) in parameters.iter().zip(args) {
+                scope.borrow_mut().set(parameter, arg);
+            }
+            CALL_DEPTH.with(|d| d.set(depth + 1));
+            let result = eval_statement(body, &scope);
+            CALL_DEPTH.with(|d| d.set(depth));
+            Ok(result?.unwrap_return())
+        },
+        Builtin(builtin) => {
+            if !builtin.arity.accepts(args.len()) {
+                return Err(wrong_argument_count(builtin.arity, args.len(), span))
+            }
+            (builtin.function)(&args).map_err(|message| RuntimeError::new(ErrorKind::InvalidArgument, message, span))
+        },
+        other => Err(RuntimeError::new(ErrorKind::NotCallable, format!("not a function: {}", other.type_name()), span)),
+    }
+}
+
+fn eval_index(left: Object, index: Object, span: Span) -> EvalResult {
+    match (left, index) {
+        (Array(ref elements), Int(i)) => Ok(usize::try_from(i).ok().and_then(|i| elements.get(i)).cloned().unwrap_or(Null)),
+        (Array(_), BigInt(_)) => Ok(Null),
+        (Hash(ref pairs), ref index) => Ok(pairs.get(&hash_key(index, span)?).cloned().unwrap_or(Null)),
+        (left, index) => Err(RuntimeError::new(ErrorKind::NotIndexable, format!("index operator not supported: {}[{}]", left.type_name(), index.type_name()), span)),
+    }
+}
+
+fn eval_prefix(prefix: &Token, right: Object, span: Span) -> EvalResult {
+    match (prefix, right) {
+        (&Token::Bang, right) => Ok(Bool(!right.is_truthy())),
+        (&Token::Minus, Int(v)) => match v.checked_neg() {
+            Some(v) => Ok(Int(v)),
+            None => Ok(Object::from(-BigInt::from(v))),
+        },
+        (&Token::Minus, BigInt(ref v)) => Ok(Object::from(-v)),
+        (&Token::Minus, Float(v)) => Ok(Float(-v)),
+        (_, right) => Err(RuntimeError::new(ErrorKind::UnknownOperator, format!("unknown operator: {}{}", prefix, right.type_name()), span)),
+    }
+}
+
+fn eval_infix(operator: &Token, left: Object, right: Object, span: Span) -> EvalResult {
+    match (left, right) {
+        (Int(l), Int(r)) => eval_integer_infix(operator, l, r, span),
+        (BigInt(ref mut l), BigInt(ref mut r)) => eval_big_integer_infix(operator, mem::take(l), mem::take(r), span),
+        (Int(l), BigInt(ref mut r)) => eval_big_integer_infix(operator, BigInt::from(l), mem::take(r), span),
+        (BigInt(ref mut l), Int(r)) => eval_big_integer_infix(operator, mem::take(l), BigInt::from(r), span),
+        // an integer meeting a float is promoted to a float
+        (Float(l), Float(r)) => eval_float_infix(operator, l, r, span),
+        (Int(l), Float(r)) => eval_float_infix(operator, l as f64, r, span),
+        (Float(l), Int(r)) => eval_float_infix(operator, l, r as f64, span),
+        (BigInt(ref l), Float(r)) => eval_float_infix(operator, to_float(l), r, span),
+        (Float(l), BigInt(ref r)) => eval_float_infix(operator, l, to_float(r), span),
+        (Str(ref mut l), Str(ref r)) => match *operator {
+            Token::Plus => Ok(Str(mem::take(l) + r)),
+            // strings order by their characters' code points
+            Token::LowerThan => Ok(Bool(*l < *r)),
+            Token::GreaterThan => Ok(Bool(*l > *r)),
+            Token::LowerThanOrEqual => Ok(Bool(*l <= *r)),
+            Token::GreaterThanOrEqual => Ok(Bool(*l >= *r)),
+            Token::Equal => Ok(Bool(*l == *r)),
+            Token::NotEqual => Ok(Bool(*l != *r)),
+            _ => Err(unknown_infix(operator, &Str(mem::take(l)), &Str(r.clone()), span)),
+        },
+        (Bool(l), Bool(r)) => match *operator {
+            Token::Equal => Ok(Bool(l == r)),
+            Token::NotEqual => Ok(Bool(l != r)),
+            _ => Err(unknown_infix(operator, &Bool(l), &Bool(r), span)),
+        },
+        (ref l, ref r) if *operator == Token::Equal => Ok(Bool(l.equals(r))),
+        (ref l, ref r) if *operator == Token::NotEqual => Ok(Bool(!l.equals(r))),
+        (ref l, ref r) if l.type_name() != r.type_name() => Err(RuntimeError::new(ErrorKind::TypeMismatch, format!("type mismatch: {} {} {}", l.type_name(), operator, r.type_name()), span)),
+        (ref l, ref r) => Err(unknown_infix(operator, l, r, span)),
+    }
+}
+
+fn eval_integer_infix(operator: &Token, left: i64, right: i64, span: Span) -> EvalResult {
+    match *operator {
+        Token::Plus => promote(left.checked_add(right), operator, left, right, span),
+        Token::Minus => promote(left.checked_sub(right), operator, left, right, span),
+        Token::Asterisk => promote(left.checked_mul(right), operator, left, right, span),
+        Token::Slash => if right == 0 {
+            Err(division_by_zero(span))
+        } else {
+            promote(left.checked_div(right), operator, left, right, span)
+        },
+        Token::Percent => if right == 0 {
+            Err(division_by_zero(span))
+        } else {
+            promote(left.checked_rem(right), operator, left, right, span)
+        },
+        Token::LowerThan => Ok(Bool(left < right)),
+        Token::GreaterThan => Ok(Bool(left > right)),
+        Token::LowerThanOrEqual => Ok(Bool(left <= right)),
+        Token::GreaterThanOrEqual => Ok(Bool(left >= right)),
+        Token::Equal => Ok(Bool(left == right)),
+        Token::NotEqual => Ok(Bool(left != right)),
+        _ => Err(unknown_infix(operator, &Int(left), &Int(right), span)),
+    }
+}
+
+/// Redoes an `i64` operation that overflowed with arbitrary precision.
+fn promote(result: Option<i64>, operator: &Token, left: i64, right: i64, span: Span) -> EvalResult {
+    match result {
+        Some(value) => Ok(Int(value)),
+        None => eval_big_integer_infix(operator, BigInt::from(left), BigInt::from(right), span),
+    }
+}
+
+/// Results are demoted back to `Int` whenever they fit. Division truncates
+/// toward zero and the remainder takes the sign of the dividend, the same as
+/// for `i64`.
+fn eval_big_integer_infix(operator: &Token, left: BigInt, right: BigInt, span: Span) -> EvalResult {
+    match *operator {
+        Token::Plus => Ok(Object::from(left + right)),
+        Token::Minus => Ok(Object::from(left - right)),
+        Token::Asterisk => Ok(Object::from(left * right)),
+        Token::Slash => if right.is_zero() {
+            Err(division_by_zero(span))
+        } else {
+            Ok(Object::from(left / right))
+        },
+        Token::Percent => if right.is_zero() {
+            Err(division_by_zero(span))
+        } else {
+            Ok(Object::from(left % right))
+        },
+        Token::LowerThan => Ok(Bool(left < right)),
+        Token::GreaterThan => Ok(Bool(left > right)),
+        Token::LowerThanOrEqual => Ok(Bool(left <= right)),
+        Token::GreaterThanOrEqual => Ok(Bool(left >= right)),
+        Token::Equal => Ok(Bool(left == right)),
+        Token::NotEqual => Ok(Bool(left != right)),
+        _ => Err(unknown_infix(operator, &Object::from(left), &Object::from(right), span)),
+    }
+}
+
+/// The nearest float, which is an infinity for integers beyond the float
+/// range.
+fn to_float(value: &BigInt) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+fn division_by_zero(span: Span) -> RuntimeError {
+    RuntimeError::new(ErrorKind::DivisionByZero, "division by zero".to_string(), span)
+}
+
+/// Float arithmetic follows IEEE 754, so dividing by zero gives an infinity
+/// or NaN rather than an error.
+fn eval_float_infix(operator: &Token, left: f64, right: f64, span: Span) -> EvalResult {
+    match *operator {
+        Token::Plus => Ok(Float(left + right)),
+        Token::Minus => Ok(Float(left - right)),
+        Token::Asterisk => Ok(Float(left * right)),
+        Token::Slash => Ok(Float(left / right)),
+        Token::Percent => Ok(Float(left % right)),
+        Token::LowerThan => Ok(Bool(left < right)),
+        Token::GreaterThan => Ok(Bool(left > right)),
+        Token::LowerThanOrEqual => Ok(Bool(left <= right)),
+        Token::GreaterThanOrEqual => Ok(Bool(left >= right)),
+        Token::Equal => Ok(Bool(left == right)),
+        Token::NotEqual => Ok(Bool(left != right)),
+        _ => Err(unknown_infix(operator, &Float(left), &Float(right), span)),
+    }
+}
+
+fn unknown_infix(operator: &Token, left: &Object, right: &Object, span: Span) -> RuntimeError {
+    RuntimeError::new(ErrorKind::UnknownOperator, format!("unknown operator: {} {} {}", left.type_name(), operator, right.type_name()), span)
+}
+
+#[cfg(test)]
+fn run(input: &str) -> EvalResult {
+    use lexer::Lexer;
+    use parser::Parser;
+    let mut parser = Parser::new(Lexer::new(input));
+    eval_program(&parser.parse_program().unwrap(), &Environment::new())
+}
 
 #[test]
 fn eval_test() {
+    assert_eq!(Ok(Int(32)), run("32"));
+    assert_eq!(Ok(Bool(true)), run("true"));
+    assert_eq!(Ok(Str("hi".to_string())), run("\"hi\""));
+}
+
+#[test]
+fn eval_prefix_test() {
+    assert_eq!(Ok(Bool(false)), run("!true"));
+    assert_eq!(Ok(Bool(true)), run("!!true"));
+    assert_eq!(Ok(Bool(false)), run("!5"));
+    assert_eq!(Ok(Int(-5)), run("-5"));
+    assert_eq!(Ok(Int(5)), run("--5"));
+}
+
+#[test]
+fn eval_infix_test() {
+    assert_eq!(Ok(Int(10)), run("5 + 5 + 5 + 5 - 10"));
+    assert_eq!(Ok(Int(50)), run("(5 + 10 * 2 + 15 / 3) * 2 + -10"));
+    assert_eq!(Ok(Bool(true)), run("1 < 2"));
+    assert_eq!(Ok(Bool(false)), run("1 > 2"));
+    assert_eq!(Ok(Bool(true)), run("(1 < 2) == true"));
+    assert_eq!(Ok(Bool(true)), run("true != false"));
+}
+
+#[test]
+fn eval_comparison_test() {
+    let cases = vec![
+        ("1 <= 1", true), ("1 <= 0", false), ("2 >= 3", false), ("3 >= 3", true),
+        ("1.5 <= 1.5", true), ("2 >= 2.5", false), ("0.5 <= 1", true),
+        ("9223372036854775807 * 2 >= 9223372036854775807 * 2", true), ("9223372036854775807 * 2 <= 1", false),
+        ("\"apple\" < \"banana\"", true), ("\"b\" <= \"a\"", false), ("\"ab\" >= \"ab\"", true), ("\"Z\" > \"a\"", false),
+        ("!(1 > 2) == (1 <= 2)", true),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(Ok(Bool(expected)), run(input), "{}", input);
+    }
+}
+
+#[test]
+fn eval_logical_test() {
+    let cases = vec![
+        ("true && true", true), ("true && false", false), ("false || true", true), ("false || false", false),
+        ("1 && \"\"", true), ("if (false) { 1 } || 0", true), ("!true || !false && false", false),
+        ("1 < 2 && 2 < 3", true), ("1 > 2 || 3 <= 2", false),
+        ("false && missing", false), ("true || missing", true),
+        ("false && 1 / 0", false), ("let n = 0; n != 0 && 10 / n > 1", false),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(Ok(Bool(expected)), run(input), "{}", input);
+    }
+    assert_eq!(Ok(Int(1)), run("let calls = 0; let f = fn() { calls += 1; true }; f() || f(); calls"));
+    let f = "let f = fn(x) { x > 0 && if (true) { return 2; }; 3 };";
+    assert_eq!(Ok(Int(2)), run(&format!("{} f(1)", f)));
+    assert_eq!(Ok(Int(3)), run(&format!("{} f(0)", f)));
+    assert_eq!(ErrorKind::UnknownIdentifier, run("true && missing").unwrap_err().kind);
+}
+
+#[test]
+fn eval_remainder_test() {
+    assert_eq!(Ok(Int(1)), run("7 % 3"));
+    assert_eq!(Ok(Int(-1)), run("-7 % 3"));
+    assert_eq!(Ok(Int(1)), run("7 % -3"));
+    assert_eq!(Ok(Int(0)), run("-9223372036854775808 % -1"));
+    assert_eq!(Ok(Int(1)), run("(9223372036854775807 * 3) % 2"));
+    assert_eq!(Ok(Float(1.5)), run("7.5 % 2"));
+    assert_eq!(Ok(Int(2)), run("let x = 17; x %= 5; x"));
+    assert_eq!(ErrorKind::DivisionByZero, run("1 % 0").unwrap_err().kind);
+    assert_eq!(ErrorKind::DivisionByZero, run("(9223372036854775807 * 2) % 0").unwrap_err().kind);
+    let evens = "let evens = []; for (i in range(7)) { if (i % 2 == 0) { evens = push(evens, i); } } evens";
+    assert_eq!(Ok(Array(Rc::new(vec![Int(0), Int(2), Int(4), Int(6)]))), run(evens));
+}
+
+#[test]
+fn eval_equality_test() {
+    let cases = vec![
+        ("if (false) { 1 } == if (false) { 2 }", true), ("{\"a\": 1}[\"b\"] == if (false) { 1 }", true), ("{\"a\": 1}[\"a\"] != if (false) { 1 }", true),
+        ("[1, [2]] == [1, [2]]", true), ("[1, 2] == [2, 1]", false), ("[] != []", false),
+        ("{\"a\": [1]} == {\"a\": [1]}", true), ("{1: 2} == {1: 3}", false),
+        ("1 == \"1\"", false), ("1 != \"1\"", true), ("true == 1", false), ("[1] == 1", false),
+        ("len == len", true), ("len == first", false),
+        ("let f = fn(x) { x }; f == f", true), ("fn(x) { x } == fn(x) { x }", false),
+        ("9223372036854775807 * 2 == 18446744073709551614.0", true),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(Ok(Bool(expected)), run(input), "{}", input);
+    }
+}
+
+#[test]
+fn eval_float_test() {
+    assert_eq!(Ok(Float(2.5)), run("2.5"));
+    assert_eq!(Ok(Float(-0.5)), run("-0.5"));
+    assert_eq!(Ok(Float(3.5)), run("1 + 2.5"));
+    assert_eq!(Ok(Float(2.5)), run("5 / 2.0"));
+    assert_eq!(Ok(Int(2)), run("5 / 2"));
+    assert_eq!(Ok(Float(0.25)), run("let total = 4; let part = 1; float(part) / total"));
+    assert_eq!(Ok(Bool(true)), run("1 == 1.0"));
+    assert_eq!(Ok(Bool(true)), run("0.1 + 0.2 != 0.3"));
+    assert_eq!(Ok(Bool(true)), run("2 < 2.5"));
+    assert_eq!(Ok(Bool(false)), run("2.5 > 3"));
+    assert_eq!(Ok(Float(f64::INFINITY)), run("1.0 / 0"));
+    assert_eq!(Ok(Str("float".to_string())), run("type(1e3)"));
+}
+
+#[test]
+fn eval_string_test() {
+    assert_eq!(Ok(Str("hello, world".to_string())), run("let name = \"world\"; \"hello, \" + name"));
+    assert_eq!(Ok(Bool(true)), run("\"a\" + \"b\" == \"ab\""));
+    assert_eq!(Ok(Bool(true)), run("\"a\" != \"b\""));
+    assert_eq!(Ok(Bool(false)), run("\"a\" == \"b\""));
+}
+
+#[test]
+fn eval_array_test() {
+    assert_eq!(Ok(Array(Rc::new(vec![Int(1), Int(4), Str("a".to_string())]))), run("[1, 2 * 2, \"a\"]"));
+    assert_eq!(Ok(Int(3)), run("let a = [1, 2, 3]; a[2]"));
+    assert_eq!(Ok(Int(6)), run("let a = [1, 2, 3]; a[0] + a[1] + a[2]"));
+    assert_eq!(Ok(Int(2)), run("[[1, 2]][0][1]"));
+    assert_eq!(Ok(Null), run("[1, 2, 3][3]"));
+    assert_eq!(Ok(Null), run("[1, 2, 3][-1]"));
+}
+
+#[test]
+fn eval_hash_test() {
+    let mut pairs = BTreeMap::new();
+    pairs.insert(HashKey::Str("one".to_string()), Int(1));
+    pairs.insert(HashKey::Int(4), Int(4));
+    pairs.insert(HashKey::Bool(true), Int(5));
+    assert_eq!(Ok(Hash(Rc::new(pairs))), run("let two = \"two\"; {\"one\": 10 - 9, 4: 4, true: 5, two: 2}[two] + 3; {\"one\": 1, 4: 4, true: 5}"));
+    assert_eq!(Ok(Int(5)), run("{\"a\": 5}[\"a\"]"));
+    assert_eq!(Ok(Null), run("{\"a\": 5}[\"b\"]"));
+    assert_eq!(Ok(Int(2)), run("{1: 1, 1: 2}[1]"));
+    assert_eq!(Ok(Int(1)), run("let h = {\"k\": [1]}; let f = fn(x) { x[\"k\"] }; f(h)[0]"));
+    assert_eq!(Ok(Int(2)), run("len({true: 1, false: 0})"));
+}
+
+#[test]
+fn eval_builtin_test() {
+    assert_eq!(Ok(Int(3)), run("len([1, 2, 3])"));
+    assert_eq!(Ok(Int(4)), run("len(\"café\")"));
+    assert_eq!(Ok(Int(1)), run("first([1, 2, 3])"));
+    assert_eq!(Ok(Null), run("first([])"));
+    assert_eq!(Ok(Int(3)), run("last([1, 2, 3])"));
+    assert_eq!(Ok(Array(Rc::new(vec![Int(2), Int(3)]))), run("rest([1, 2, 3])"));
+    assert_eq!(Ok(Null), run("rest([])"));
+    assert_eq!(Ok(Array(Rc::new(vec![Int(1)]))), run("let a = []; let b = push(a, 1); push(a, 2); b"));
+    assert_eq!(Ok(Int(0)), run("let a = []; push(a, 1); len(a)"));
+    assert_eq!(Ok(Int(7)), run("let len = fn(x) { 7 }; len([])"));
+    assert_eq!(Ok(Int(2)), run("let size = len; let apply = fn(f, x) { f(x) }; apply(size, [1, 2])"));
+    assert_eq!(Ok(Str("builtin".to_string())), run("type(len)"));
+    assert_eq!(Ok(Str("integer".to_string())), run("type(int(\"12\") + 1)"));
+    assert_eq!(Ok(Str("[1, 2]".to_string())), run("str([1, 2])"));
+    assert_eq!(Ok(Bool(false)), run("bool(if (false) { 1 })"));
+    assert_eq!(Ok(Null), run("puts()"));
+}
 
-    assert_eq!(Int(32), eval(IntegerExpression{ value: 32 }));
-    assert_eq!(Str("foo".to_string()), eval(IdentifierExpression{ value: "foo".to_string() }));
-    assert_eq!(Bool(true), eval(BooleanExpression{ value: true }));
-}
\ No newline at end of file
+#[test]
+fn eval_if_test() {
+    assert_eq!(Ok(Int(10)), run("if (true) { 10 }"));
+    assert_eq!(Ok(Null), run("if (false) { 10 }"));
+    assert_eq!(Ok(Int(10)), run("if (1 < 2) { 10 } else { 20 }"));
+    assert_eq!(Ok(Int(20)), run("if (1 > 2) { 10 } else { 20 }"));
+    let grade = "let grade = fn(n) { if (n > 89) { \"a\" } else if (n > 79) { \"b\" } else if (n > 69) { \"c\" } else { \"f\" } };";
+    assert_eq!(Ok(Str("a".to_string())), run(&format!("{} grade(95)", grade)));
+    assert_eq!(Ok(Str("c".to_string())), run(&format!("{} grade(75)", grade)));
+    assert_eq!(Ok(Str("f".to_string())), run(&format!("{} grade(5)", grade)));
+    assert_eq!(Ok(Null), run("if (false) { 1 } else if (false) { 2 }"));
+    assert_eq!(Ok(Int(2)), run("let f = fn() { if (false) { 1 } else if (true) { return 2; } 3 }; f()"));
+}
+
+#[test]
+fn eval_return_test() {
+    assert_eq!(Ok(Int(10)), run("return 10; 9;"));
+    assert_eq!(Ok(Int(10)), run("9; return 2 * 5; 9;"));
+    assert_eq!(Ok(Int(10)), run("if (10 > 1) { if (10 > 1) { return 10; } return 1; }"));
+}
+
+#[test]
+fn eval_let_test() {
+    assert_eq!(Ok(Int(5)), run("let a = 5; a;"));
+    assert_eq!(Ok(Int(15)), run("let a = 5; let b = a; let c = a + b + 5; c;"));
+}
+
+#[test]
+fn eval_call_test() {
+    assert_eq!(Ok(Int(5)), run("let identity = fn(x) { x; }; identity(5);"));
+    assert_eq!(Ok(Int(10)), run("let double = fn(x) { return x * 2; 0; }; double(5);"));
+    assert_eq!(Ok(Int(20)), run("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));"));
+    assert_eq!(Ok(Int(7)), run("let seven = fn() { 7 }; seven();"));
+    assert_eq!(Ok(Int(120)), run("let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5);"));
+}
+
+#[test]
+fn eval_call_callee_test() {
+    assert_eq!(Ok(Int(5)), run("fn(x) { x }(5)"));
+    assert_eq!(Ok(Int(3)), run("let adder = fn(x) { fn(y) { x + y } }; adder(1)(2)"));
+    assert_eq!(Ok(Int(6)), run("let fns = [fn(x) { x * 2 }, len]; fns[0](3)"));
+    assert_eq!(Ok(Int(2)), run("let ops = {\"len\": len}; ops[\"len\"]([1, 2])"));
+    assert_eq!(Ok(Int(10)), run("let twice = fn(f) { fn(x) { f(f(x)) } }; twice(fn(x) { x + 3 })(4)"));
+    assert_eq!(ErrorKind::NotCallable, run("[1][0](2)").unwrap_err().kind);
+}
+
+#[test]
+fn eval_loop_test() {
+    assert_eq!(Ok(Int(10)), run("let sum = fn(xs) { let total = 0; for (x in xs) { let total = total + x; } total }; sum([1, 2, 3, 4])"));
+    assert_eq!(Ok(Int(45)), run("let total = 0; for (i in range(10)) { let total = total + i; } total"));
+    assert_eq!(Ok(Int(9)), run("let total = 0; for (i in range(2, 5)) { let total = total + i; } total"));
+    assert_eq!(Ok(Null), run("let n = 0; for (i in range(5, 2)) { let n = n + 1; }"));
+    assert_eq!(Ok(Str("abc".to_string())), run("let s = \"\"; for (k in {\"c\": 3, \"a\": 1, \"b\": 2}) { let s = s + k; } s"));
+    assert_eq!(Ok(Int(4)), run("let i = 0; while (i < 4) { let i = i + 1; } i"));
+    assert_eq!(Ok(Int(3)), run("let i = 0; while (true) { if (i == 3) { break; } let i = i + 1; } i"));
+    assert_eq!(Ok(Int(35)), run("let total = 0; for (i in range(10)) { if (i < 5) { continue; } let total = total + i; } total"));
+    assert_eq!(Ok(Int(6)), run("let pairs = 0; for (i in range(4)) { for (j in range(4)) { if (i < j + 1) { continue; } let pairs = pairs + 1; } } pairs"));
+    assert_eq!(Ok(Int(2)), run("let find = fn(xs, y) { let i = 0; for (x in xs) { if (x == y) { return i; } let i = i + 1; } -1 }; find([5, 6, 7], 7)"));
+    assert_eq!(Ok(Int(1)), run("let f = fn() { while (true) { while (true) { return 1; } } }; f()"));
+    assert_eq!(Ok(Int(2)), run("let x = 0; for (i in [1, 2, 3]) { let x = if (i > 2) { break; } else { i }; } x"));
+    assert_eq!(Ok(Int(2)), run("for (x in [1, 2]) { x } x"));
+    assert_eq!(Ok(Str("range(0, 3)".to_string())), run("str(range(3))"));
+}
+
+#[test]
+fn eval_assign_test() {
+    assert_eq!(Ok(Int(2)), run("let x = 1; x = 2; x"));
+    assert_eq!(Ok(Null), run("let x = 1; x = 2;"));
+    assert_eq!(Ok(Int(10)), run("let total = 0; for (i in range(5)) { total += i; } total"));
+    assert_eq!(Ok(Int(3)), run("let i = 0; while (i < 3) { i += 1; } i"));
+    assert_eq!(Ok(Int(4)), run("let x = 10; x -= 2; x /= 4; x *= 2; x"));
+    assert_eq!(Ok(Str("ab".to_string())), run("let s = \"a\"; s += \"b\"; s"));
+    assert_eq!(Ok(Int(2)), run("let counter = fn() { let n = 0; fn() { n += 1; n } }; let next = counter(); next(); next()"));
+    assert_eq!(Ok(Int(1)), run("let x = 1; let f = fn() { let x = 5; x = 6; }; f(); x"));
+    assert_eq!(Ok(Array(Rc::new(vec![Int(1), Int(9), Int(3)]))), run("let a = [1, 2, 3]; a[1] = 9; a"));
+    assert_eq!(Ok(Array(Rc::new(vec![Int(1), Int(2)]))), run("let a = [1, 2]; let b = a; a[0] = 5; b"));
+    assert_eq!(Ok(Int(3)), run("let a = [1, 2]; let s = 0; for (x in a) { a[1] = 10; s += x; } s"));
+    assert_eq!(Ok(Int(7)), run("let a = [[1, 2], [3, 4]]; a[1][0] += 4; a[1][0]"));
+    assert_eq!(Ok(Int(3)), run("let h = {\"a\": 1}; h[\"b\"] = 2; h[\"a\"] += 2; h[\"a\"]"));
+    assert_eq!(Ok(Int(1)), run("let h = {}; h[\"b\"] = 2; len(h)"));
+    assert_eq!(Ok(Int(1)), run("let h = {\"xs\": [0]}; h[\"xs\"][0] = 1; h[\"xs\"][0]"));
+    assert_eq!(Ok(Int(2)), run("let f = fn() { for (x in [1, 2]) { if (x == 2) { return x; } } }; f()"));
+}
+
+#[test]
+fn eval_assign_in_place_test() {
+    use lexer::Lexer;
+    use parser::Parser;
+    let env = Environment::new();
+    let eval_input = |input: &str| eval_program(&Parser::new(Lexer::new(input)).parse_program().unwrap(), &env);
+    let elements = |env: &Env| match env.borrow().get("a") {
+        Some(Array(ref elements)) => Rc::as_ptr(elements),
+        _ => panic!("`a` should be an array"),
+    };
+    eval_input("let a = [[1], 2]; let h = {};").unwrap();
+    let before = elements(&env);
+    eval_input("a[0][0] = 5; a[1] += 1;").unwrap();
+    assert_eq!(before, elements(&env));
+    assert!(eval_input("h[\"x\"] += 1").is_err());
+    assert_eq!(Ok(Int(0)), eval_input("len(h)"));
+    assert!(eval_input("a[1] += \"x\"").is_err());
+    assert_eq!(Ok(Int(3)), eval_input("a[1]"));
+}
+
+#[test]
+fn eval_invalid_assign_test() {
+    let target = IntegerExpression { value: 1.into(), span: Span::default() };
+    let value = IntegerExpression { value: 2.into(), span: Span::default() };
+    let program = Program::new(vec![AssignStatement { target, operator: None, value, span: Span::default() }]);
+    let error = eval_program(&program, &Environment::new()).unwrap_err();
+    assert_eq!(ErrorKind::InvalidAssignmentTarget, error.kind);
+}
+
+#[test]
+fn eval_closure_test() {
+    assert_eq!(Ok(Int(4)), run("let adder = fn(x) { fn(y) { x + y } }; let addTwo = adder(2); addTwo(2);"));
+    assert_eq!(Ok(Int(3)), run("let x = 1; let f = fn() { x }; let g = fn(x) { f() + x }; g(2);"));
+    assert_eq!(ErrorKind::UnknownIdentifier, run("let f = fn() { let inner = 1; }; f(); inner;").unwrap_err().kind);
+}
+
+#[test]
+fn eval_error_test() {
+    let cases = vec![
+        ("5 + true;", ErrorKind::TypeMismatch, "type mismatch: integer + boolean"),
+        ("5 + true; 5;", ErrorKind::TypeMismatch, "type mismatch: integer + boolean"),
+        ("-true", ErrorKind::UnknownOperator, "unknown operator: -boolean"),
+        ("true + false;", ErrorKind::UnknownOperator, "unknown operator: boolean + boolean"),
+        ("if (10 > 1) { if (10 > 1) { return true + false; } return 1; }", ErrorKind::UnknownOperator, "unknown operator: boolean + boolean"),
+        ("foobar", ErrorKind::UnknownIdentifier, "identifier not found: foobar"),
+        ("let x = 1; x(1);", ErrorKind::NotCallable, "not a function: integer"),
+        ("let f = fn(x) { x }; f();", ErrorKind::WrongArgumentCount, "wrong number of arguments: expected 1, got 0"),
+        ("5 / 0", ErrorKind::DivisionByZero, "division by zero"),
+        ("\"a\" - \"b\"", ErrorKind::UnknownOperator, "unknown operator: string - string"),
+        ("\"a\" % \"b\"", ErrorKind::UnknownOperator, "unknown operator: string % string"),
+        ("true <= false", ErrorKind::UnknownOperator, "unknown operator: boolean <= boolean"),
+        ("1 >= \"a\"", ErrorKind::TypeMismatch, "type mismatch: integer >= string"),
+        ("[1] < [2]", ErrorKind::UnknownOperator, "unknown operator: array < array"),
+        ("if (false) { 1 } + if (false) { 1 }", ErrorKind::UnknownOperator, "unknown operator: null + null"),
+        ("\"a\" + 1", ErrorKind::TypeMismatch, "type mismatch: string + integer"),
+        ("1[0]", ErrorKind::NotIndexable, "index operator not supported: integer[integer]"),
+        ("[1][true]", ErrorKind::NotIndexable, "index operator not supported: array[boolean]"),
+        ("{[1]: 2}", ErrorKind::UnhashableKey, "unusable as hash key: array"),
+        ("{1.5: 2}", ErrorKind::UnhashableKey, "unusable as hash key: float"),
+        ("1.5 + true", ErrorKind::TypeMismatch, "type mismatch: float + boolean"),
+        ("{1: 2}[fn(x) { x }]", ErrorKind::UnhashableKey, "unusable as hash key: function"),
+        ("len(1)", ErrorKind::InvalidArgument, "argument to `len` not supported, got integer"),
+        ("push(1, 2)", ErrorKind::InvalidArgument, "argument to `push` not supported, got integer"),
+        ("len([1], [2])", ErrorKind::WrongArgumentCount, "wrong number of arguments: expected 1, got 2"),
+        ("push([1])", ErrorKind::WrongArgumentCount, "wrong number of arguments: expected 2, got 1"),
+        ("int(\"x\")", ErrorKind::InvalidArgument, "cannot convert \"x\" to an integer"),
+        ("len + 1", ErrorKind::TypeMismatch, "type mismatch: builtin + integer"),
+        ("for (x in 5) { x }", ErrorKind::NotIterable, "cannot iterate over integer"),
+        ("x = 1", ErrorKind::UnknownIdentifier, "identifier not found: x"),
+        ("len = 1", ErrorKind::UnknownIdentifier, "identifier not found: len"),
+        ("let f = fn() { y += 1 }; f()", ErrorKind::UnknownIdentifier, "identifier not found: y"),
+        ("let x = 1; x += true", ErrorKind::TypeMismatch, "type mismatch: integer + boolean"),
+        ("let a = [1]; a[1] = 2", ErrorKind::IndexOutOfRange, "index 1 out of range for array of length 1"),
+        ("let a = [1]; a[-1] = 2", ErrorKind::IndexOutOfRange, "index -1 out of range for array of length 1"),
+        ("let a = 1; a[0] = 2", ErrorKind::NotIndexable, "index operator not supported: integer[integer]"),
+        ("let h = {}; h[[1]] = 2", ErrorKind::UnhashableKey, "unusable as hash key: array"),
+        ("let h = {}; h[\"n\"] += 1", ErrorKind::TypeMismatch, "type mismatch: null + integer"),
+        ("range(1, \"a\")", ErrorKind::InvalidArgument, "argument to `range` not supported, got string"),
+        ("range(1.5)", ErrorKind::InvalidArgument, "argument to `range` not supported, got float"),
+        ("range()", ErrorKind::WrongArgumentCount, "wrong number of arguments: expected 1 to 2, got 0"),
+    ];
+    for (input, kind, message) in cases {
+        let error = run(input).unwrap_err();
+        assert_eq!(kind, error.kind);
+        assert_eq!(message, error.to_string());
+    }
+}
+
+#[test]
+fn eval_error_span_test() {
+    let error = run("let x = 1;\nlet y = x + true;").unwrap_err();
+    assert_eq!((2, 9), (error.span.start.line, error.span.start.column));
+    assert_eq!((2, 17), (error.span.end.line, error.span.end.column));
+}
+
+#[test]
+fn eval_call_depth_test() {
+    let input = format!("let f = fn(n) {{ if (n < 1) {{ 0 }} else {{ 1 + f(n - 1) }} }}; f({})", MAX_CALL_DEPTH - 1);
+    assert_eq!(Ok(Int(MAX_CALL_DEPTH as i64 - 1)), run(&input));
+    assert_eq!(ErrorKind::CallDepthExceeded, run("let f = fn(n) { f(n + 1) }; f(0)").unwrap_err().kind);
+    assert_eq!(Ok(Int(1)), run("let f = fn(n) { n }; f(1)"));
+
+    // calls that each nest deeply run out of depth long before they run
+    // out of calls
+    let body = format!("{}f(n + 1){}", "if (true) { ".repeat(100), " }".repeat(100));
+    let input = format!("let f = fn(n) {{ {} }}; f(0)", body);
+    assert_eq!(ErrorKind::DepthExceeded, run(&input).unwrap_err().kind);
+    assert_eq!(Ok(Int(2)), run("let f = fn(n) { n }; f(2)"));
+}
+
+#[test]
+fn eval_small_stack_test() {
+    use parser::MAX_NESTING;
+    // evaluation grows its own stack, so the limits hold on a thread with
+    // the default 2MB stack too, as do programs nested as deep as they parse
+    ::std::thread::Builder::new().stack_size(2 * 1024 * 1024).spawn(|| {
+        let input = "let f = fn(n) { if (true) { if (true) { if (true) { f(n + 1) } } } }; f(0)";
+        assert_eq!(ErrorKind::CallDepthExceeded, run(input).unwrap_err().kind);
+        let body = format!("{}f(n + 1){}", "if (true) { ".repeat(5), " }".repeat(5));
+        let input = format!("let f = fn(n) {{ {} }}; f(0)", body);
+        assert_eq!(ErrorKind::DepthExceeded, run(&input).unwrap_err().kind);
+        let input = format!("{}1{}", "[".repeat(100), "]".repeat(100));
+        assert!(run(&input).is_ok());
+
+        let sum = vec!["1"; MAX_NESTING - 1].join(" + ");
+        assert_eq!(Ok(Int(MAX_NESTING as i64 - 1)), run(&sum));
+        let ladder = format!("let a = false; {} else {{ 0 }}", vec!["if (a) { 1 }"; MAX_NESTING - 2].join(" else "));
+        assert_eq!(Ok(Int(0)), run(&ladder));
+        assert!(run(&format!("fn() {{ {} }}", ladder)).unwrap().to_string().ends_with("else { 0;  };  }"));
+    }).unwrap().join().unwrap();
+}
+
+#[test]
+fn eval_deeply_nested_value_test() {
+    assert_eq!(Ok(Bool(true)), run("let a = []; let b = []; for (i in range(100000)) { a = [a]; b = [b]; } a == b"));
+    assert_eq!(Ok(Int(1)), run("let h = {}; for (i in range(100000)) { h = {1: [h]}; } len(h)"));
+    assert_eq!(Ok(Int(1)), run("let f = fn(g) { fn() { g } }; let x = 0; for (i in range(100000)) { x = f(x); } 1"));
+    assert!(matches!(run("let a = []; for (i in range(100000)) { a = [a]; } str(a)"), Ok(Str(ref s)) if s.contains("[...]")));
+}
+
+#[test]
+fn eval_big_integer_test() {
+    let big = |digits: &str| Ok(BigInt(digits.parse().unwrap()));
+    assert_eq!(Ok(Int(i64::MAX)), run("9223372036854775807"));
+    assert_eq!(Ok(Int(i64::MIN)), run("-9223372036854775808"));
+    assert_eq!(big("9223372036854775808"), run("9223372036854775807 + 1"));
+    assert_eq!(big("-9223372036854775809"), run("-9223372036854775808 - 1"));
+    assert_eq!(big("18446744073709551616"), run("4294967296 * 4294967296"));
+    assert_eq!(big("9223372036854775808"), run("-9223372036854775808 / -1"));
+    assert_eq!(big("9223372036854775808"), run("--9223372036854775808"));
+    assert_eq!(big("9223372036854775808"), run("9223372036854775808"));
+    assert_eq!(big("-99999999999999999999"), run("-99999999999999999999"));
+    assert_eq!(Ok(Int(1)), run("99999999999999999999 - 99999999999999999998"));
+    assert_eq!(big("15511210043330985984000000"), run("let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(25)"));
+    assert_eq!(Ok(Int(i64::MAX)), run("9223372036854775807 + 1 - 1"));
+    assert_eq!(Ok(Int(-1)), run("let big = 9223372036854775807 * 2; big / -big"));
+    assert_eq!(Ok(Bool(true)), run("9223372036854775807 * 2 > 9223372036854775807"));
+    assert_eq!(Ok(Bool(true)), run("-9223372036854775807 * 2 < 1"));
+    assert_eq!(Ok(Bool(true)), run("9223372036854775807 * 2 == 2 * 9223372036854775807"));
+    assert_eq!(Ok(Float(1.8446744073709552e19)), run("9223372036854775807 * 2 + 2.0"));
+    assert_eq!(Ok(Str("integer".to_string())), run("type(9223372036854775807 * 2)"));
+    assert_eq!(Ok(Str("a".to_string())), run("let h = {9223372036854775807 * 2: \"a\"}; h[9223372036854775807 + 9223372036854775807]"));
+    assert_eq!(Ok(Null), run("[1][9223372036854775807 * 2]"));
+    assert_eq!(ErrorKind::DivisionByZero, run("9223372036854775807 * 2 / 0").unwrap_err().kind);
+}
@@ -4,37 +4,110 @@
 use token;
 use token::Token;
 
-use std::str::Chars;
-use std::iter::Peekable;
+use token::Position;
+use token::Span;
+use token::SpannedToken;
+
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexError {
+    UnexpectedCharacter(char),
+    FloatTooLarge(String),
+    UnterminatedString,
+    UnknownEscape(char),
+    InvalidUnicodeEscape(String),
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LexError::UnexpectedCharacter(ch) => write!(f, "unexpected character `{}`", ch.escape_debug()),
+            LexError::FloatTooLarge(ref literal) => write!(f, "float literal `{}` is too large", literal),
+            LexError::UnterminatedString => write!(f, "unterminated string literal"),
+            LexError::UnknownEscape(ch) => write!(f, "unknown escape sequence `\\{}`", ch.escape_debug()),
+            LexError::InvalidUnicodeEscape(ref digits) => write!(f, "invalid unicode escape `\\u{{{}}}`", digits),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Lexer<'a> {
-    input: Peekable<Chars<'a>>
+    input: &'a str,
+    position: Position,
+    /// Added to the offset of every span, see `with_offset`.
+    base: usize,
+    error: Option<LexError>,
 }
 
 impl<'a> Lexer<'a> {
 
-    pub fn new(input: &str) -> Lexer {
-        Lexer { input: input.chars().peekable() }
+    pub fn new(input: &'a str) -> Lexer<'a> {
+        let mut lexer = Lexer { input, position: Position::default(), base: 0, error: None };
+        lexer.skip_shebang();
+        lexer
     }
 
-    fn read_char(&mut self) -> Option<char> {
-        self.input.next()
+    /// Lexes `input` as if it started `offset` bytes into a larger text, so
+    /// the spans of separately lexed inputs do not overlap. Lines and columns
+    /// still count from the start of `input`.
+    pub fn with_offset(input: &'a str, offset: usize) -> Lexer<'a> {
+        Lexer { base: offset, ..Lexer::new(input) }
+    }
+
+    /// Skips a `#!` interpreter line so scripts can be made executable.
+    fn skip_shebang(&mut self) {
+        if self.input.starts_with("#!") {
+            while let Some(c) = self.peek_char() {
+                if c == '\n' {
+                    break;
+                }
+                self.read_char();
+            }
+        }
     }
 
-    fn peek_char(&mut self) -> Option<&char> {
-        self.input.peek()
+    /// Why the most recent `Token::Illegal` was produced, if it has not been
+    /// taken yet.
+    pub fn take_error(&mut self) -> Option<LexError> {
+        self.error.take()
     }
 
-    fn peek_char_eq(&mut self, ch: char) -> bool {
-        match self.peek_char() {
-            Some(&c) => c == ch,
-            None => false,
+    fn located(&self, position: Position) -> Position {
+        Position { offset: self.base + position.offset, ..position }
+    }
+
+    fn illegal(&mut self, error: LexError) -> Token {
+        self.error = Some(error);
+        Token::Illegal
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        let ch = self.input[self.position.offset..].chars().next()?;
+        self.position.offset += ch.len_utf8();
+        if ch == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
         }
+        Some(ch)
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.position.offset..].chars().next()
+    }
+
+    fn peek_nth_char(&self, n: usize) -> Option<char> {
+        self.input[self.position.offset..].chars().nth(n)
+    }
+
+    fn peek_char_eq(&self, ch: char) -> bool {
+        self.peek_char() == Some(ch)
     }
 
     fn skip_whitespace(&mut self) {
-        while let Some(&c) = self.peek_char() {
+        while let Some(c) = self.peek_char() {
             if c.is_whitespace() {
                 self.read_char();
             } else {
@@ -43,9 +116,9 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn peek_is_letter(&mut self) -> bool {
+    fn peek_is_letter(&self) -> bool {
         match self.peek_char() {
-            Some(&ch) => is_letter(ch),
+            Some(ch) => is_letter(ch),
             None => false,
         }
     }
@@ -54,31 +127,148 @@ impl<'a> Lexer<'a> {
         let mut identifier = String::new();
         identifier.push(first);
         while self.peek_is_letter() {
-            let ch = self.read_char().unwrap();
-            identifier.push(ch);
+            if let Some(ch) = self.read_char() {
+                identifier.push(ch);
+            }
         }
         identifier
     }
 
-    fn read_number(&mut self, first: char) -> u32 {
-        let mut number = String::new();
-        number.push(first);
-        while let Some(&c) = self.peek_char() {
-            if c.is_numeric() {
-                let n = self.read_char().unwrap();
-                number.push(n);
+    fn read_digits(&mut self, number: &mut String) {
+        while let Some(c) = self.peek_char() {
+            if c.is_ascii_digit() {
+                self.read_char();
+                number.push(c);
             } else {
                 break;
             }
         }
-        number.parse::<u32>().unwrap()
+    }
+
+    /// Reads an integer, or a float when the digits are followed by a
+    /// fraction such as `.5` or an exponent such as `e-3`. A `.` or `e` that
+    /// no digit follows is left for the next token.
+    fn read_number(&mut self, first: char) -> Token {
+        let mut number = String::new();
+        number.push(first);
+        self.read_digits(&mut number);
+
+        let mut float = false;
+        if self.peek_char_eq('.') && self.peek_nth_char(1).is_some_and(|c| c.is_ascii_digit()) {
+            float = true;
+            self.read_char();
+            number.push('.');
+            self.read_digits(&mut number);
+        }
+        if let Some(e) = self.peek_char().filter(|&c| c == 'e' || c == 'E') {
+            let sign = self.peek_nth_char(1).filter(|&c| c == '+' || c == '-');
+            let digits_at = if sign.is_some() { 2 } else { 1 };
+            if self.peek_nth_char(digits_at).is_some_and(|c| c.is_ascii_digit()) {
+                float = true;
+                self.read_char();
+                number.push(e);
+                if let Some(sign) = sign {
+                    self.read_char();
+                    number.push(sign);
+                }
+                self.read_digits(&mut number);
+            }
+        }
+
+        if float {
+            match number.parse::<f64>() {
+                Ok(value) if value.is_finite() => Token::Float(value),
+                _ => self.illegal(LexError::FloatTooLarge(number)),
+            }
+        } else {
+            Token::Integer(number.parse().expect("a run of digits is an integer"))
+        }
+    }
+
+    /// Reads a string literal after its opening quote. Literals may not span
+    /// lines, and the first bad escape makes the whole literal illegal.
+    fn read_string(&mut self) -> Token {
+        let mut value = String::new();
+        let mut error = None;
+        loop {
+            match self.peek_char() {
+                None | Some('\n') => return self.illegal(LexError::UnterminatedString),
+                Some('"') => {
+                    self.read_char();
+                    break
+                },
+                Some('\\') => {
+                    self.read_char();
+                    match self.read_escape() {
+                        Ok(ch) => value.push(ch),
+                        Err(e) => error = error.or(Some(e)),
+                    }
+                },
+                Some(ch) => {
+                    self.read_char();
+                    value.push(ch);
+                },
+            }
+        }
+        match error {
+            Some(e) => self.illegal(e),
+            None => Token::Str(value),
+        }
+    }
+
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        match self.peek_char() {
+            None | Some('\n') => return Err(LexError::UnterminatedString),
+            _ => {},
+        }
+        match self.read_char() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('0') => Ok('\0'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('u') => self.read_unicode_escape(),
+            Some(ch) => Err(LexError::UnknownEscape(ch)),
+            None => Err(LexError::UnterminatedString),
+        }
+    }
+
+    /// Reads the `{1F600}` part of a `\u{1F600}` escape.
+    fn read_unicode_escape(&mut self) -> Result<char, LexError> {
+        if !self.peek_char_eq('{') {
+            return Err(LexError::InvalidUnicodeEscape(String::new()))
+        }
+        self.read_char();
+        let mut digits = String::new();
+        while let Some(ch) = self.peek_char() {
+            if ch == '}' || ch == '"' || ch == '\n' {
+                break
+            }
+            self.read_char();
+            digits.push(ch);
+        }
+        if !self.peek_char_eq('}') || digits.is_empty() || digits.len() > 6 {
+            return Err(LexError::InvalidUnicodeEscape(digits))
+        }
+        self.read_char();
+        match u32::from_str_radix(&digits, 16).ok().and_then(::std::char::from_u32) {
+            Some(ch) => Ok(ch),
+            None => Err(LexError::InvalidUnicodeEscape(digits)),
+        }
     }
 
     pub fn next_token(&mut self) -> Token {
+        self.next_spanned_token().token
+    }
+
+    pub fn next_spanned_token(&mut self) -> SpannedToken {
 
         self.skip_whitespace();
 
-        match self.read_char() {
+        let start = self.located(self.position);
+
+        let token = match self.read_char() {
             Some('=') => {
                 if self.peek_char_eq('=') {
                     self.read_char();
@@ -87,8 +277,22 @@ impl<'a> Lexer<'a> {
                     Token::Assign
                 }
             },
-            Some('+') => Token::Plus,
-            Some('-') => Token::Minus,
+            Some('+') => {
+                if self.peek_char_eq('=') {
+                    self.read_char();
+                    Token::PlusAssign
+                } else {
+                    Token::Plus
+                }
+            },
+            Some('-') => {
+                if self.peek_char_eq('=') {
+                    self.read_char();
+                    Token::MinusAssign
+                } else {
+                    Token::Minus
+                }
+            },
             Some('!') => {
                 if self.peek_char_eq('=') {
                     self.read_char();
@@ -97,29 +301,78 @@ impl<'a> Lexer<'a> {
                     Token::Bang
                 }
             },
-            Some('/') => Token::Slash,
-            Some('*') => Token::Asterisk,
-            Some('<') => Token::LowerThan,
-            Some('>') => Token::GreaterThan,
+            Some('/') => {
+                if self.peek_char_eq('=') {
+                    self.read_char();
+                    Token::SlashAssign
+                } else {
+                    Token::Slash
+                }
+            },
+            Some('*') => {
+                if self.peek_char_eq('=') {
+                    self.read_char();
+                    Token::AsteriskAssign
+                } else {
+                    Token::Asterisk
+                }
+            },
+            Some('%') => {
+                if self.peek_char_eq('=') {
+                    self.read_char();
+                    Token::PercentAssign
+                } else {
+                    Token::Percent
+                }
+            },
+            Some('<') => {
+                if self.peek_char_eq('=') {
+                    self.read_char();
+                    Token::LowerThanOrEqual
+                } else {
+                    Token::LowerThan
+                }
+            },
+            Some('>') => {
+                if self.peek_char_eq('=') {
+                    self.read_char();
+                    Token::GreaterThanOrEqual
+                } else {
+                    Token::GreaterThan
+                }
+            },
+            Some('&') if self.peek_char_eq('&') => {
+                self.read_char();
+                Token::And
+            },
+            Some('|') if self.peek_char_eq('|') => {
+                self.read_char();
+                Token::Or
+            },
             Some('(') => Token::LeftParenthesis,
             Some(')') => Token::RightParenthesis,
             Some('{') => Token::LeftBrace,
             Some('}') => Token::RightBrace,
+            Some('[') => Token::LeftBracket,
+            Some(']') => Token::RightBracket,
             Some(',') => Token::Comma,
+            Some(':') => Token::Colon,
             Some(';') => Token::Semicolon,
-            Some(ch @ _) => {
+            Some('"') => self.read_string(),
+            Some(ch) => {
                 if is_letter(ch) {
                     let literal = self.read_identifier(ch);
                     token::lookup_identifier(&literal)
-                } else if ch.is_numeric() {
-                    let number = self.read_number(ch);
-                    Token::Integer(number)
+                } else if ch.is_ascii_digit() {
+                    self.read_number(ch)
                 } else {
-                    Token::Illegal
+                    self.illegal(LexError::UnexpectedCharacter(ch))
                 }
             },
             None => Token::EndOfFile,
-        }
+        };
+
+        SpannedToken { token, span: Span::new(start, self.located(self.position)) }
     }
 }
 
@@ -153,12 +406,12 @@ fn next_token_test() {
     assert_eq!(Token::Let, lexer.next_token());
     assert_eq!(Token::Identifier("five".to_string()), lexer.next_token());
     assert_eq!(Token::Assign, lexer.next_token());
-    assert_eq!(Token::Integer(5), lexer.next_token());
+    assert_eq!(Token::Integer(5.into()), lexer.next_token());
     assert_eq!(Token::Semicolon, lexer.next_token());
     assert_eq!(Token::Let, lexer.next_token());
     assert_eq!(Token::Identifier("ten".to_string()), lexer.next_token());
     assert_eq!(Token::Assign, lexer.next_token());
-    assert_eq!(Token::Integer(10), lexer.next_token());
+    assert_eq!(Token::Integer(10.into()), lexer.next_token());
     assert_eq!(Token::Semicolon, lexer.next_token());
     assert_eq!(Token::Let, lexer.next_token());
     assert_eq!(Token::Identifier("add".to_string()), lexer.next_token());
@@ -190,20 +443,20 @@ fn next_token_test() {
     assert_eq!(Token::Minus, lexer.next_token());
     assert_eq!(Token::Slash, lexer.next_token());
     assert_eq!(Token::Asterisk, lexer.next_token());
-    assert_eq!(Token::Integer(5), lexer.next_token());
+    assert_eq!(Token::Integer(5.into()), lexer.next_token());
     assert_eq!(Token::Semicolon, lexer.next_token());
-    assert_eq!(Token::Integer(5), lexer.next_token());
+    assert_eq!(Token::Integer(5.into()), lexer.next_token());
     assert_eq!(Token::LowerThan, lexer.next_token());
-    assert_eq!(Token::Integer(10), lexer.next_token());
+    assert_eq!(Token::Integer(10.into()), lexer.next_token());
     assert_eq!(Token::GreaterThan, lexer.next_token());
-    assert_eq!(Token::Integer(5), lexer.next_token());
+    assert_eq!(Token::Integer(5.into()), lexer.next_token());
     assert_eq!(Token::Semicolon, lexer.next_token());
 
     assert_eq!(Token::If, lexer.next_token());
     assert_eq!(Token::LeftParenthesis, lexer.next_token());
-    assert_eq!(Token::Integer(5), lexer.next_token());
+    assert_eq!(Token::Integer(5.into()), lexer.next_token());
     assert_eq!(Token::LowerThan, lexer.next_token());
-    assert_eq!(Token::Integer(10), lexer.next_token());
+    assert_eq!(Token::Integer(10.into()), lexer.next_token());
     assert_eq!(Token::RightParenthesis, lexer.next_token());
     assert_eq!(Token::LeftBrace, lexer.next_token());
     assert_eq!(Token::Return, lexer.next_token());
@@ -217,13 +470,133 @@ fn next_token_test() {
     assert_eq!(Token::Semicolon, lexer.next_token());
     assert_eq!(Token::RightBrace, lexer.next_token());
 
-    assert_eq!(Token::Integer(10), lexer.next_token());
+    assert_eq!(Token::Integer(10.into()), lexer.next_token());
     assert_eq!(Token::Equal, lexer.next_token());
-    assert_eq!(Token::Integer(10), lexer.next_token());
+    assert_eq!(Token::Integer(10.into()), lexer.next_token());
     assert_eq!(Token::Semicolon, lexer.next_token());
 
-    assert_eq!(Token::Integer(10), lexer.next_token());
+    assert_eq!(Token::Integer(10.into()), lexer.next_token());
     assert_eq!(Token::NotEqual, lexer.next_token());
-    assert_eq!(Token::Integer(9), lexer.next_token());
+    assert_eq!(Token::Integer(9.into()), lexer.next_token());
     assert_eq!(Token::Semicolon, lexer.next_token());
 }
+
+#[test]
+fn next_spanned_token_test() {
+    let mut lexer = Lexer::new("let x =\n  10 == y[0];");
+    let expected = vec![
+        (Token::Let, (0, 1, 1), (3, 1, 4)),
+        (Token::Identifier("x".to_string()), (4, 1, 5), (5, 1, 6)),
+        (Token::Assign, (6, 1, 7), (7, 1, 8)),
+        (Token::Integer(10.into()), (10, 2, 3), (12, 2, 5)),
+        (Token::Equal, (13, 2, 6), (15, 2, 8)),
+        (Token::Identifier("y".to_string()), (16, 2, 9), (17, 2, 10)),
+        (Token::LeftBracket, (17, 2, 10), (18, 2, 11)),
+        (Token::Integer(0.into()), (18, 2, 11), (19, 2, 12)),
+        (Token::RightBracket, (19, 2, 12), (20, 2, 13)),
+        (Token::Semicolon, (20, 2, 13), (21, 2, 14)),
+        (Token::EndOfFile, (21, 2, 14), (21, 2, 14)),
+    ];
+    for (token, start, end) in expected {
+        let spanned = lexer.next_spanned_token();
+        assert_eq!(token, spanned.token);
+        assert_eq!((start.0, start.1, start.2), (spanned.span.start.offset, spanned.span.start.line, spanned.span.start.column));
+        assert_eq!((end.0, end.1, end.2), (spanned.span.end.offset, spanned.span.end.line, spanned.span.end.column));
+    }
+
+    let mut lexer = Lexer::with_offset("\n x", 100);
+    let span = lexer.next_spanned_token().span;
+    assert_eq!((102, 2, 2), (span.start.offset, span.start.line, span.start.column));
+    assert_eq!((103, 2, 3), (span.end.offset, span.end.line, span.end.column));
+}
+
+#[test]
+fn operator_test() {
+    let mut lexer = Lexer::new("+= -= *= /= %= + = -- =/ == <= >= < = && || % &");
+    let expected = vec![
+        Token::PlusAssign, Token::MinusAssign, Token::AsteriskAssign, Token::SlashAssign, Token::PercentAssign,
+        Token::Plus, Token::Assign, Token::Minus, Token::Minus, Token::Assign, Token::Slash, Token::Equal,
+        Token::LowerThanOrEqual, Token::GreaterThanOrEqual, Token::LowerThan, Token::Assign,
+        Token::And, Token::Or, Token::Percent, Token::Illegal,
+        Token::EndOfFile,
+    ];
+    for token in expected {
+        assert_eq!(token, lexer.next_token());
+    }
+}
+
+#[test]
+fn lex_error_test() {
+    let mut lexer = Lexer::new("99999999999999999999: @");
+    assert_eq!(Token::Integer("99999999999999999999".parse().unwrap()), lexer.next_token());
+    assert_eq!(Token::Colon, lexer.next_token());
+    assert_eq!(None, lexer.take_error());
+    assert_eq!(Token::Illegal, lexer.next_token());
+    assert_eq!(Some(LexError::UnexpectedCharacter('@')), lexer.take_error());
+    assert_eq!(None, lexer.take_error());
+}
+
+#[test]
+fn skip_shebang_test() {
+    let mut lexer = Lexer::new("#!/usr/bin/env interpreter\nlet");
+    let spanned = lexer.next_spanned_token();
+    assert_eq!(Token::Let, spanned.token);
+    assert_eq!((2, 1), (spanned.span.start.line, spanned.span.start.column));
+    assert_eq!(Token::EndOfFile, Lexer::new("#!").next_token());
+    assert_eq!(Token::Illegal, Lexer::new(" #!").next_token());
+}
+
+#[test]
+fn read_string_test() {
+    let mut lexer = Lexer::new(r#""hello world" "a\n\t\"\\b\0" "\u{41}\u{1F600}" """#);
+    assert_eq!(Token::Str("hello world".to_string()), lexer.next_token());
+    assert_eq!(Token::Str("a\n\t\"\\b\0".to_string()), lexer.next_token());
+    assert_eq!(Token::Str("A\u{1F600}".to_string()), lexer.next_token());
+    assert_eq!(Token::Str(String::new()), lexer.next_token());
+    assert_eq!(Token::EndOfFile, lexer.next_token());
+}
+
+#[test]
+fn string_error_test() {
+    let cases = vec![
+        ("\"abc", LexError::UnterminatedString, (0, 4)),
+        ("\"abc\ndef\"", LexError::UnterminatedString, (0, 4)),
+        ("\"abc\\", LexError::UnterminatedString, (0, 5)),
+        ("\"a\\qb\\z\"", LexError::UnknownEscape('q'), (0, 8)),
+        ("\"\\u{110000}\"", LexError::InvalidUnicodeEscape("110000".to_string()), (0, 12)),
+        ("\"\\u{zz}\"", LexError::InvalidUnicodeEscape("zz".to_string()), (0, 8)),
+        ("\"\\u41\"", LexError::InvalidUnicodeEscape(String::new()), (0, 6)),
+    ];
+    for (input, error, (start, end)) in cases {
+        let mut lexer = Lexer::new(input);
+        let spanned = lexer.next_spanned_token();
+        assert_eq!(Token::Illegal, spanned.token, "{}", input);
+        assert_eq!(Some(error), lexer.take_error(), "{}", input);
+        assert_eq!((start, end), (spanned.span.start.offset, spanned.span.end.offset), "{}", input);
+    }
+    assert_eq!("unknown escape sequence `\\q`", LexError::UnknownEscape('q').to_string());
+    assert_eq!("invalid unicode escape `\\u{zz}`", LexError::InvalidUnicodeEscape("zz".to_string()).to_string());
+}
+
+#[test]
+fn read_float_test() {
+    let mut lexer = Lexer::new("2.75 0.5 1e3 2.5E-2 7e+1 1. 2.x 3e 4ex");
+    assert_eq!(Token::Float(2.75), lexer.next_token());
+    assert_eq!(Token::Float(0.5), lexer.next_token());
+    assert_eq!(Token::Float(1000.0), lexer.next_token());
+    assert_eq!(Token::Float(0.025), lexer.next_token());
+    assert_eq!(Token::Float(70.0), lexer.next_token());
+    assert_eq!(Token::Integer(1.into()), lexer.next_token());
+    assert_eq!(Token::Illegal, lexer.next_token());
+    assert_eq!(Token::Integer(2.into()), lexer.next_token());
+    assert_eq!(Token::Illegal, lexer.next_token());
+    assert_eq!(Token::Identifier("x".to_string()), lexer.next_token());
+    assert_eq!(Token::Integer(3.into()), lexer.next_token());
+    assert_eq!(Token::Identifier("e".to_string()), lexer.next_token());
+    assert_eq!(Token::Integer(4.into()), lexer.next_token());
+    assert_eq!(Token::Identifier("ex".to_string()), lexer.next_token());
+
+    let mut lexer = Lexer::new("1e400");
+    assert_eq!(Token::Illegal, lexer.next_token());
+    assert_eq!(Some(LexError::FloatTooLarge("1e400".to_string())), lexer.take_error());
+}
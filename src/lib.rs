@@ -1,6 +1,13 @@
+extern crate num_bigint;
+extern crate num_traits;
+extern crate stacker;
+
 pub mod token;
 pub mod lexer;
 pub mod ast;
 pub mod parser;
 pub mod object;
+pub mod builtins;
+pub mod environment;
 pub mod evaluator;
+pub mod diagnostics;
@@ -1,38 +1,214 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
+extern crate interpreter;
+extern crate rustyline;
 
+use std::env;
+use std::fs;
 use std::io;
-use std::io::BufRead;
+use std::io::IsTerminal;
+use std::io::Read;
 use std::io::Write;
+use std::process;
 
-pub mod lexer;
-use lexer::Lexer;
+mod repl;
 
-pub mod token;
-use token::Token;
+use interpreter::diagnostics::Diagnostic;
+use interpreter::environment::Environment;
+use interpreter::evaluator;
+use interpreter::lexer::Lexer;
+use interpreter::object::Object;
+use interpreter::parser::Parser;
 
-pub mod ast;
+const USAGE: &str = "\
+usage: interpreter [--error-format human|json] [run <file> | -e <code> | -]
 
-pub mod parser;
-use parser::Parser;
+With no arguments, starts a REPL when stdin is a terminal and otherwise runs
+the program read from stdin. `-e` prints the value of the program unless it
+is null.
 
-fn main() {
+exit status: 0 on success, 1 on a runtime error, 2 on a syntax error, 64 on a
+usage error and 66 when the input cannot be read.";
+
+const EXIT_RUNTIME_ERROR: i32 = 1;
+const EXIT_SYNTAX_ERROR: i32 = 2;
+const EXIT_USAGE: i32 = 64;
+const EXIT_NO_INPUT: i32 = 66;
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Repl,
+    Help,
+    File(String),
+    Eval(String),
+    Stdin,
+}
 
-    let stdin = io::stdin();
+#[derive(Debug, PartialEq)]
+struct Options {
+    mode: Mode,
+    json: bool,
+}
 
-    loop {
-        print!(">> ");
-        io::stdout().flush().expect("Error flushing stdout");
+/// Where diagnostics go: rendered for people, or one JSON object per line
+/// for tools.
+struct Reporter {
+    json: bool,
+    color: bool,
+}
 
-        let mut line = String::new();
-        stdin.lock().read_line(&mut line).expect("Error reading from stdin");
+impl Reporter {
 
-        let lexer = Lexer::new(&line);
-        let mut parser = Parser::new(lexer);
-        let program = parser.parse_program();
+    fn report(&self, file: &str, source: &str, diagnostic: &Diagnostic) {
+        if self.json {
+            eprintln!("{}", diagnostic.to_json(file));
+        } else {
+            eprint!("{}", diagnostic.render(file, source, self.color));
+        }
+    }
+}
 
-        println!("{}", program);
+fn parse_args(args: &[String], interactive: bool) -> Result<Options, String> {
+    let mut mode = None;
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let next = match arg.as_str() {
+            "-h" | "--help" => Mode::Help,
+            "--error-format" => {
+                json = match args.next().map(String::as_str) {
+                    Some("human") => false,
+                    Some("json") => true,
+                    _ => return Err("`--error-format` expects `human` or `json`".to_string()),
+                };
+                continue
+            },
+            "run" => match args.next() {
+                Some(path) => Mode::File(path.clone()),
+                None => return Err("`run` expects a file".to_string()),
+            },
+            "-e" => match args.next() {
+                Some(code) => Mode::Eval(code.clone()),
+                None => return Err("`-e` expects code".to_string()),
+            },
+            "-" => Mode::Stdin,
+            other => return Err(format!("unexpected argument `{}`", other)),
+        };
+        if mode.is_some() {
+            return Err(format!("unexpected argument `{}`", arg));
+        }
+        mode = Some(next);
     }
+    let mode = mode.unwrap_or(if interactive { Mode::Repl } else { Mode::Stdin });
+    Ok(Options { mode, json })
+}
 
+/// Parses and evaluates `source` in a fresh environment, reporting any
+/// errors, and returns the process exit status.
+fn run(file: &str, source: &str, print_result: bool, reporter: &Reporter) -> i32 {
+    let program = match Parser::new(Lexer::new(source)).parse_program() {
+        Ok(program) => program,
+        Err(errors) => {
+            for error in &errors {
+                reporter.report(file, source, &Diagnostic::from(error));
+            }
+            return EXIT_SYNTAX_ERROR
+        },
+    };
+    match evaluator::eval_program(&program, &Environment::new()) {
+        Ok(Object::Null) => 0,
+        Ok(value) => {
+            if !print_result {
+                return 0
+            }
+            match writeln!(io::stdout().lock(), "{}", value) {
+                Ok(()) => 0,
+                // the reader has gone away, as with `| head`, and wants no more
+                Err(ref error) if error.kind() == io::ErrorKind::BrokenPipe => 0,
+                Err(error) => {
+                    eprintln!("error: cannot write to stdout: {}", error);
+                    EXIT_RUNTIME_ERROR
+                },
+            }
+        },
+        Err(error) => {
+            reporter.report(file, source, &Diagnostic::from(&error));
+            EXIT_RUNTIME_ERROR
+        },
+    }
 }
 
+/// Carries out `mode`, returning the process exit status.
+fn execute(mode: Mode, reporter: &Reporter) -> i32 {
+    match mode {
+        Mode::Help => {
+            println!("{}", USAGE);
+            0
+        },
+        Mode::Repl => match repl::run(reporter) {
+            Ok(()) => 0,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                EXIT_NO_INPUT
+            },
+        },
+        Mode::Eval(code) => run("<-e>", &code, true, reporter),
+        Mode::File(path) => match fs::read_to_string(&path) {
+            Ok(source) => run(&path, &source, false, reporter),
+            Err(error) => {
+                eprintln!("error: cannot read `{}`: {}", path, error);
+                EXIT_NO_INPUT
+            },
+        },
+        Mode::Stdin => {
+            let mut source = String::new();
+            match io::stdin().read_to_string(&mut source) {
+                Ok(_) => run("<stdin>", &source, false, reporter),
+                Err(error) => {
+                    eprintln!("error: cannot read stdin: {}", error);
+                    EXIT_NO_INPUT
+                },
+            }
+        },
+    }
+}
+
+fn main() {
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args, io::stdin().is_terminal()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(EXIT_USAGE);
+        },
+    };
+    let reporter = Reporter { json: options.json, color: !options.json && io::stderr().is_terminal() };
+
+    process::exit(execute(options.mode, &reporter));
+}
+
+#[cfg(test)]
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+#[test]
+fn parse_args_test() {
+    let options = |mode, json| Ok(Options { mode, json });
+    assert_eq!(options(Mode::Repl, false), parse_args(&[], true));
+    assert_eq!(options(Mode::Stdin, false), parse_args(&[], false));
+    assert_eq!(options(Mode::Stdin, false), parse_args(&args(&["-"]), true));
+    assert_eq!(options(Mode::File("a.mk".to_string()), true), parse_args(&args(&["--error-format", "json", "run", "a.mk"]), true));
+    assert_eq!(options(Mode::Eval("1".to_string()), false), parse_args(&args(&["-e", "1"]), true));
+    assert_eq!(options(Mode::Help, false), parse_args(&args(&["--help"]), true));
+    assert_eq!(Err("`run` expects a file".to_string()), parse_args(&args(&["run"]), true));
+    assert_eq!(Err("unexpected argument `-e`".to_string()), parse_args(&args(&["run", "a.mk", "-e", "1"]), true));
+    assert_eq!(Err("`--error-format` expects `human` or `json`".to_string()), parse_args(&args(&["--error-format", "xml"]), true));
+}
+
+#[test]
+fn run_test() {
+    let reporter = Reporter { json: false, color: false };
+    assert_eq!(0, run("t.mk", "#!/usr/bin/env interpreter\nlet x = 1; x + 1;", false, &reporter));
+    assert_eq!(EXIT_SYNTAX_ERROR, run("t.mk", "let = 1;", false, &reporter));
+    assert_eq!(EXIT_RUNTIME_ERROR, run("t.mk", "1 + true;", false, &reporter));
+}
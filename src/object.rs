@@ -1,29 +1,266 @@
 #![allow(unused_imports)]
 #![allow(dead_code)]
 
+use std::collections::BTreeMap;
 use std::fmt;
+use std::mem;
+use std::rc::Rc;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use ast::Statement;
+use builtins::Builtin;
+use environment::Env;
 use self::Object::*;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Object {
     Int(i64),
+    /// An integer outside the `i64` range; see `From<BigInt>`.
+    BigInt(BigInt),
+    Float(f64),
     Str(String),
     Bool(bool),
+    /// Arrays and hashes are shared between copies until one is written to.
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+    Function {
+        parameters: Vec<String>,
+        body: Rc<Statement>,
+        env: Env,
+    },
+    Builtin(&'static Builtin),
+    /// A half-open run of integers, as returned by `range`.
+    Range {
+        start: i64,
+        end: i64,
+    },
+    Return(Box<Object>),
+    Break,
+    Continue,
     Null,
 }
 
+impl Object {
+
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Int(_) | BigInt(_) => "integer",
+            Float(_) => "float",
+            Str(_) => "string",
+            Bool(_) => "boolean",
+            Array(_) => "array",
+            Hash(_) => "hash",
+            Function{ .. } => "function",
+            Builtin(_) => "builtin",
+            Range{ .. } => "range",
+            Return(ref v) => v.type_name(),
+            Break => "break",
+            Continue => "continue",
+            Null => "null",
+        }
+    }
+
+    /// How the REPL shows a value: like `Display`, but with strings quoted so
+    /// `"1"` and `1` can be told apart.
+    pub fn inspect(&self) -> String {
+        match *self {
+            Str(ref v) => format!("{:?}", v),
+            Return(ref v) => v.inspect(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// The value carried by a `return`, or this value if it is not one.
+    pub fn unwrap_return(mut self) -> Object {
+        match self {
+            Return(ref mut value) => mem::replace(&mut **value, Null),
+            _ => self,
+        }
+    }
+
+    /// Whether this is a `return`, `break` or `continue` on its way out to
+    /// the function or loop that handles it.
+    pub fn is_unwinding(&self) -> bool {
+        matches!(*self, Return(_) | Break | Continue)
+    }
+
+    /// Equality as `==` sees it: integers and floats compare by value, arrays
+    /// and hashes element by element, functions by identity, and values of
+    /// different types are never equal. Elements are compared from a work
+    /// list rather than recursively, as values may nest arbitrarily deep.
+    pub fn equals(&self, other: &Object) -> bool {
+        let mut pending = vec![(self, other)];
+        while let Some(pair) = pending.pop() {
+            let equal = match pair {
+                (&Int(l), &Float(r)) | (&Float(r), &Int(l)) => l as f64 == r,
+                (BigInt(l), &Float(r)) | (&Float(r), BigInt(l)) => l.to_f64() == Some(r),
+                (Array(l), Array(r)) if l.len() == r.len() => {
+                    pending.extend(l.iter().zip(r.iter()));
+                    true
+                },
+                (Hash(l), Hash(r)) if l.len() == r.len() && l.keys().eq(r.keys()) => {
+                    pending.extend(l.values().zip(r.values()));
+                    true
+                },
+                (Array(_), Array(_)) | (Hash(_), Hash(_)) => false,
+                (Function{ body: l, env: l_env, .. }, Function{ body: r, env: r_env, .. }) => Rc::ptr_eq(l, r) && Rc::ptr_eq(l_env, r_env),
+                (l, r) => l == r,
+            };
+            if !equal {
+                return false
+            }
+        }
+        true
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match *self {
+            Bool(v) => v,
+            Null => false,
+            _ => true,
+        }
+    }
+}
+
+/// Arrays, hashes and closures can nest arbitrarily deep, so dropping one
+/// takes apart what only it holds from a work list instead of recursing.
+impl Drop for Object {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        take_contents(self, &mut pending);
+        while let Some(mut object) = pending.pop() {
+            take_contents(&mut object, &mut pending);
+        }
+    }
+}
+
+/// Moves the values that `object` alone holds into `pending`.
+fn take_contents(object: &mut Object, pending: &mut Vec<Object>) {
+    match *object {
+        Array(ref mut elements) => if let Some(elements) = Rc::get_mut(elements) {
+            pending.append(elements);
+        },
+        Hash(ref mut pairs) => if let Some(pairs) = Rc::get_mut(pairs) {
+            pending.extend(mem::take(pairs).into_values());
+        },
+        Function{ ref mut env, .. } => if let Some(env) = Rc::get_mut(env) {
+            pending.extend(env.get_mut().take_values());
+        },
+        Return(ref mut value) => pending.push(mem::replace(&mut **value, Null)),
+        _ => {},
+    }
+}
+
+/// Integers only become `BigInt` when they do not fit an `i64`, so every
+/// integer has exactly one representation and equal values compare equal.
+impl From<BigInt> for Object {
+    fn from(value: BigInt) -> Object {
+        match value.to_i64() {
+            Some(value) => Int(value),
+            None => BigInt(value),
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Object {
+        match key {
+            HashKey::Int(v) => Int(v),
+            HashKey::BigInt(v) => BigInt(v),
+            HashKey::Str(v) => Str(v),
+            HashKey::Bool(v) => Bool(v),
+        }
+    }
+}
+
+/// The values that can be used as hash keys. Keys are ordered so hashes
+/// print the same way every time.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub enum HashKey {
+    Int(i64),
+    BigInt(BigInt),
+    Str(String),
+    Bool(bool),
+}
+
+impl HashKey {
+
+    pub fn from_object(object: &Object) -> Option<HashKey> {
+        match *object {
+            Int(v) => Some(HashKey::Int(v)),
+            BigInt(ref v) => Some(HashKey::BigInt(v.clone())),
+            Str(ref v) => Some(HashKey::Str(v.clone())),
+            Bool(v) => Some(HashKey::Bool(v)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HashKey::Int(v) => write!(f, "{}", v),
+            HashKey::BigInt(ref v) => write!(f, "{}", v),
+            HashKey::Str(ref v) => write!(f, "{:?}", v),
+            HashKey::Bool(v) => write!(f, "{}", v),
+        }
+    }
+}
+
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Int(v) => write!(f, "{}", v.to_string()),
-            Str(ref v) => write!(f, "{}", v.to_string()),
-            Bool(v) => write!(f, "{}", v.to_string()),
+            Int(v) => write!(f, "{}", v),
+            BigInt(ref v) => write!(f, "{}", v),
+            // always with a `.` or an exponent, so floats never read as integers
+            Float(v) => write!(f, "{:?}", v),
+            Str(ref v) => write!(f, "{}", v),
+            Bool(v) => write!(f, "{}", v),
+            Array(_) | Hash(_) => write_nested(self, f, 0),
+            Function{ ref parameters, ref body, .. } => write!(f, "fn({}) {}", parameters.join(", "), body),
+            Builtin(builtin) => write!(f, "builtin {}", builtin.name),
+            Range{ start, end } => write!(f, "range({}, {})", start, end),
+            Return(ref v) => write!(f, "{}", v),
+            Break => write!(f, "break"),
+            Continue => write!(f, "continue"),
             Null => write!(f, "null"),
         }
 
     }
 }
 
+/// Arrays and hashes nested deeper than this print as `[...]` and `{...}`.
+pub const MAX_PRINT_DEPTH: usize = 100;
+
+/// Writes `object` as an element `depth` containers deep: strings quoted, as
+/// `inspect` does, and containers past `MAX_PRINT_DEPTH` elided.
+fn write_nested(object: &Object, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+    match *object {
+        Str(ref v) if depth > 0 => write!(f, "{:?}", v),
+        Array(ref elements) if elements.is_empty() => write!(f, "[]"),
+        Hash(ref pairs) if pairs.is_empty() => write!(f, "{{}}"),
+        Array(_) if depth >= MAX_PRINT_DEPTH => write!(f, "[...]"),
+        Hash(_) if depth >= MAX_PRINT_DEPTH => write!(f, "{{...}}"),
+        Array(ref elements) => {
+            write!(f, "[")?;
+            for (i, element) in elements.iter().enumerate() {
+                write!(f, "{}", if i == 0 { "" } else { ", " })?;
+                write_nested(element, f, depth + 1)?;
+            }
+            write!(f, "]")
+        },
+        Hash(ref pairs) => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                write!(f, "{}{}: ", if i == 0 { "" } else { ", " }, key)?;
+                write_nested(value, f, depth + 1)?;
+            }
+            write!(f, "}}")
+        },
+        Return(ref v) => write_nested(v, f, depth),
+        _ => write!(f, "{}", object),
+    }
+}
 
 #[test]
 #[ignore]
@@ -34,3 +271,86 @@ fn object_to_string_test() {
     assert_eq!("false".to_string(), Bool(false).to_string());
     assert_eq!("null".to_string(), Null.to_string());
 }
+
+#[test]
+fn float_to_string_test() {
+    assert_eq!("12.0", Float(12.0).to_string());
+    assert_eq!("0.1", Float(0.1).to_string());
+    assert_eq!("-2.5", Float(-2.5).to_string());
+    assert_eq!("1e21", Float(1e21).to_string());
+    assert_eq!("inf", Float(f64::INFINITY).to_string());
+    assert_eq!("[1.0, 2]", Array(Rc::new(vec![Float(1.0), Int(2)])).to_string());
+}
+
+#[test]
+fn equals_test() {
+    assert!(Null.equals(&Null));
+    assert!(Int(1).equals(&Float(1.0)));
+    assert!(!Int(1).equals(&Str("1".to_string())));
+    assert!(!Bool(false).equals(&Null));
+    assert!(Array(Rc::new(vec![Int(1), Array(Rc::new(vec![Null]))])).equals(&Array(Rc::new(vec![Float(1.0), Array(Rc::new(vec![Null]))]))));
+    assert!(!Array(Rc::new(vec![Int(1)])).equals(&Array(Rc::new(vec![Int(1), Int(2)]))));
+    let mut pairs = BTreeMap::new();
+    pairs.insert(HashKey::Str("a".to_string()), Int(1));
+    assert!(Hash(Rc::new(pairs.clone())).equals(&Hash(Rc::new(pairs.clone()))));
+    pairs.insert(HashKey::Str("b".to_string()), Null);
+    assert!(!Hash(Rc::new(pairs)).equals(&Hash(Rc::default())));
+    assert!(!Float(f64::NAN).equals(&Float(f64::NAN)));
+}
+
+#[test]
+fn deeply_nested_test() {
+    let mut value = Array(Rc::default());
+    for _ in 0..200_000 {
+        value = Array(Rc::new(vec![value]));
+    }
+    assert!(value.equals(&value.clone()));
+    assert!(!value.equals(&Array(Rc::new(vec![Array(Rc::default())]))));
+    let expected = format!("{}[...]{}", "[".repeat(MAX_PRINT_DEPTH), "]".repeat(MAX_PRINT_DEPTH));
+    assert_eq!(expected, value.to_string());
+
+    let mut pairs = BTreeMap::new();
+    pairs.insert(HashKey::Int(1), Array(Rc::new(vec![Str("a".to_string())])));
+    assert_eq!("[{1: [\"a\"]}, {}, []]", Array(Rc::new(vec![Hash(Rc::new(pairs)), Hash(Rc::default()), Array(Rc::default())])).to_string());
+}
+
+#[test]
+fn is_truthy_test() {
+    assert!(Int(0).is_truthy());
+    assert!(Bool(true).is_truthy());
+    assert!(!Bool(false).is_truthy());
+    assert!(!Null.is_truthy());
+}
+
+#[test]
+fn from_big_int_test() {
+    assert_eq!(Int(-5), Object::from(BigInt::from(-5)));
+    assert_eq!(Int(i64::MIN), Object::from(BigInt::from(i64::MIN)));
+    let big = BigInt::from(u64::MAX);
+    assert_eq!(BigInt(big.clone()), Object::from(big.clone()));
+    assert_eq!("18446744073709551615", Object::from(big).to_string());
+}
+
+#[test]
+fn inspect_test() {
+    assert_eq!("12", Int(12).inspect());
+    assert_eq!("\"a\\\"b\\n\"", Str("a\"b\n".to_string()).inspect());
+    assert_eq!("null", Null.inspect());
+    assert_eq!("[1, \"a\", [true]]", Array(Rc::new(vec![Int(1), Str("a".to_string()), Array(Rc::new(vec![Bool(true)]))])).inspect());
+}
+
+#[test]
+fn hash_key_test() {
+    assert_eq!(Some(HashKey::Str("a".to_string())), HashKey::from_object(&Str("a".to_string())));
+    assert_eq!(Some(HashKey::Int(1)), HashKey::from_object(&Int(1)));
+    assert_eq!(Some(HashKey::BigInt(BigInt::from(u64::MAX))), HashKey::from_object(&Object::from(BigInt::from(u64::MAX))));
+    assert_eq!(None, HashKey::from_object(&Array(Rc::default())));
+    assert_eq!(Str("a".to_string()), Object::from(HashKey::Str("a".to_string())));
+    assert_ne!(HashKey::from_object(&Int(1)), HashKey::from_object(&Bool(true)));
+
+    let mut pairs = BTreeMap::new();
+    pairs.insert(HashKey::Str("b".to_string()), Int(2));
+    pairs.insert(HashKey::Bool(true), Null);
+    pairs.insert(HashKey::Int(1), Str("a".to_string()));
+    assert_eq!("{1: \"a\", \"b\": 2, true: null}", Hash(Rc::new(pairs)).to_string());
+}
@@ -2,6 +2,7 @@
 #![allow(dead_code)]
 
 use lexer::Lexer;
+use lexer::LexError;
 use token::Token;
 use token::Token::*;
 use ast;
@@ -10,26 +11,98 @@ use ast::Statement::*;
 use ast::Expression;
 use ast::Expression::*;
 use ast::Program;
+use ast::RED_ZONE;
+use ast::STACK_SEGMENT;
+use token::Span;
+use std::fmt;
+use std::rc::Rc;
 use self::Precedence::*;
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expected {
+    Token(Token),
+    Expression,
+    Identifier,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expected::Token(ref t) => write!(f, "`{}`", t),
+            Expected::Expression => write!(f, "expression"),
+            Expected::Identifier => write!(f, "identifier"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseErrorKind {
+    Unexpected {
+        expected: Expected,
+        found: Token,
+    },
+    Lex(LexError),
+    TooDeeplyNested,
+    /// A `break` or `continue` that is not inside the body of a loop.
+    OutsideLoop(Token),
+    /// An assignment to something other than a name or an index into one.
+    InvalidAssignmentTarget,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::Unexpected{ ref expected, found: EndOfFile } => write!(f, "expected {}, found end of input", expected),
+            ParseErrorKind::Unexpected{ ref expected, ref found } => write!(f, "expected {}, found `{}`", expected, found),
+            ParseErrorKind::Lex(ref error) => write!(f, "{}", error),
+            ParseErrorKind::TooDeeplyNested => write!(f, "expression nested more than {} levels deep", MAX_NESTING),
+            ParseErrorKind::OutsideLoop(ref keyword) => write!(f, "`{}` outside of a loop", keyword),
+            ParseErrorKind::InvalidAssignmentTarget => write!(f, "invalid assignment target"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How deeply expressions may nest, counting every operator of a chain such
+/// as `1 + 2 + 3` and every `else if` as one level. Parsing, printing and
+/// evaluating grow the stack as they recurse, but dropping the AST does not,
+/// so its depth is bounded by what a 2MB thread can drop.
+pub const MAX_NESTING: usize = 2048;
+
 #[derive(Debug)]
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     current_token: Token,
+    current_span: Span,
     peek_token: Token,
+    peek_span: Span,
+    errors: Vec<ParseError>,
+    depth: usize,
+    loops: usize,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(lexer: Lexer) -> Parser {
-        let mut p = Parser { lexer: lexer, current_token: Token::Illegal, peek_token: Token::Illegal };
+    pub fn new(lexer: Lexer<'a>) -> Parser<'a> {
+        let mut p = Parser { lexer, current_token: Token::Illegal, current_span: Span::default(), peek_token: Token::Illegal, peek_span: Span::default(), errors: Vec::new(), depth: 0, loops: 0 };
         p.next_token();
         p.next_token();
         p
     }
 
     fn next_token(&mut self) {
-        self.current_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+        let next = self.lexer.next_spanned_token();
+        if let Some(error) = self.lexer.take_error() {
+            self.errors.push(ParseError { kind: ParseErrorKind::Lex(error), span: next.span });
+        }
+        self.current_token = std::mem::replace(&mut self.peek_token, next.token);
+        self.current_span = std::mem::replace(&mut self.peek_span, next.span);
     }
 
     fn current_token_is(&mut self, t: Token) -> bool {
@@ -41,146 +114,368 @@ impl<'a> Parser<'a> {
     }
 
     fn expect_peek(&mut self, t: Token) -> bool {
-        if self.peek_token_is(t) {
+        if self.peek_token_is(t.clone()) {
             self.next_token();
             true
         } else {
-            // TODO error!
+            self.peek_error(Expected::Token(t));
             false
         }
     }
 
-    pub fn parse_program(&mut self) -> Program {
+    fn peek_error(&mut self, expected: Expected) {
+        let (found, span) = (self.peek_token.clone(), self.peek_span);
+        self.unexpected(expected, found, span);
+    }
+
+    fn current_error(&mut self, expected: Expected) {
+        let (found, span) = (self.current_token.clone(), self.current_span);
+        self.unexpected(expected, found, span);
+    }
+
+    fn unexpected(&mut self, expected: Expected, found: Token, span: Span) {
+        // the lexer has already reported why the token is illegal
+        if found != Illegal {
+            self.errors.push(ParseError { kind: ParseErrorKind::Unexpected { expected, found }, span });
+        }
+    }
+
+    fn enter_nesting(&mut self) -> bool {
+        if self.depth >= MAX_NESTING {
+            self.errors.push(ParseError { kind: ParseErrorKind::TooDeeplyNested, span: self.current_span });
+            false
+        } else {
+            self.depth += 1;
+            true
+        }
+    }
+
+    pub fn parse_program(&mut self) -> Result<Program, Vec<ParseError>> {
+        let (program, errors) = self.parse_program_partial();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses the whole input even when it contains errors, returning every
+    /// statement that could be recovered together with all errors found.
+    pub fn parse_program_partial(&mut self) -> (Program, Vec<ParseError>) {
         let mut statements: Vec<Statement> = Vec::new();
         while self.current_token != EndOfFile {
-            let stmt = self.parse_statement();
-            match stmt {
-                Some(s) => statements.push(s),
-                None => {},
+            self.parse_next_statement(&mut statements);
+        }
+        let mut errors = self.errors.split_off(0);
+        errors.sort_by_key(|e| e.span.start.offset);
+        (Program::new(statements), errors)
+    }
+
+    fn parse_next_statement(&mut self, statements: &mut Vec<Statement>) {
+        let start = self.current_span;
+        let errors = self.errors.len();
+        match self.parse_statement() {
+            Some(statement) => {
+                statements.push(statement);
+                self.next_token();
+            },
+            None if self.errors.len() == errors => self.next_token(),
+            None => self.synchronize(start),
+        }
+    }
+
+    /// Skips the rest of a statement that failed to parse: past the next `;`,
+    /// or up to a `let`, `return`, `while`, `for` or the `}` closing the
+    /// enclosing block, ignoring anything inside nested parentheses, braces
+    /// and brackets.
+    fn synchronize(&mut self, start: Span) {
+        if self.current_span == start {
+            // a stray closing delimiter is an error on its own
+            let stray = matches!(self.current_token, RightParenthesis | RightBrace | RightBracket | Comma);
+            self.next_token();
+            if stray {
+                return
+            }
+        }
+        let mut depth = 0usize;
+        loop {
+            match self.current_token {
+                EndOfFile => return,
+                Let | Return | While | For | RightBrace if depth == 0 => return,
+                Semicolon if depth == 0 => {
+                    self.next_token();
+                    return
+                },
+                LeftParenthesis | LeftBrace | LeftBracket => depth += 1,
+                RightParenthesis | RightBrace | RightBracket => depth = depth.saturating_sub(1),
+                _ => {},
             }
             self.next_token();
         }
-        Program::new(statements)
     }
 
     fn parse_statement(&mut self) -> Option<Statement> {
         match self.current_token {
+            Semicolon => None,
             Token::Let => self.parse_let_statement(),
             Token::Return => self.parse_return_statement(),
+            Token::While => self.parse_while_statement(),
+            Token::For => self.parse_for_statement(),
+            Token::Break | Token::Continue => self.parse_loop_control(),
             _ => self.parse_expression_statement(),
         }
     }
 
     fn parse_let_statement(&mut self) -> Option<Statement> {
+        let start = self.current_span;
         if let Identifier(name) = self.peek_token.clone() {
             self.next_token();
             if !self.expect_peek(Token::Assign) {
                 None
             } else {
                 self.next_token();
-                let value = self.parse_expression(Lowest).unwrap();
-                let stmt = LetStatement{ name: name.clone(), value: value };
+                let value = self.parse_expression(Lowest)?;
                 if self.peek_token_is(Semicolon) {
                     self.next_token();
                 }
-                Some(stmt)
+                Some(LetStatement{ name: name.clone(), value, span: start.to(self.current_span) })
             }
         } else {
+            self.peek_error(Expected::Identifier);
             None
         }
     }
 
     fn parse_return_statement(&mut self) -> Option<Statement> {
+        let start = self.current_span;
+        self.next_token();
+        let value = self.parse_expression(Lowest)?;
+
+        if self.peek_token_is(Semicolon) {
+            self.next_token();
+        }
+
+        Some(ReturnStatement{ value, span: start.to(self.current_span) })
+    }
+
+    fn parse_while_statement(&mut self) -> Option<Statement> {
+        let start = self.current_span;
+        if !self.expect_peek(LeftParenthesis) {
+            return None
+        }
+        self.next_token();
+        let condition = self.parse_expression(Lowest)?;
+        if !self.expect_peek(RightParenthesis) {
+            return None
+        }
+        let body = self.parse_loop_body()?;
+        Some(WhileStatement{ condition, body: Box::new(body), span: start.to(self.current_span) })
+    }
+
+    fn parse_for_statement(&mut self) -> Option<Statement> {
+        let start = self.current_span;
+        if !self.expect_peek(LeftParenthesis) {
+            return None
+        }
+        let variable = match self.peek_token.clone() {
+            Identifier(name) => name,
+            _ => {
+                self.peek_error(Expected::Identifier);
+                return None
+            },
+        };
         self.next_token();
-        let value = self.parse_expression(Lowest).unwrap();
-        let stmt = ReturnStatement{ value: value};
+        if !self.expect_peek(In) {
+            return None
+        }
+        self.next_token();
+        let iterable = self.parse_expression(Lowest)?;
+        if !self.expect_peek(RightParenthesis) {
+            return None
+        }
+        let body = self.parse_loop_body()?;
+        Some(ForStatement{ variable, iterable, body: Box::new(body), span: start.to(self.current_span) })
+    }
+
+    /// Parses the block of a loop, within which `break` and `continue` are
+    /// allowed. Loops count towards `MAX_NESTING` like expressions do.
+    fn parse_loop_body(&mut self) -> Option<Statement> {
+        if !self.expect_peek(LeftBrace) {
+            return None
+        }
+        let depth = self.depth;
+        if !self.enter_nesting() {
+            return None
+        }
+        self.loops += 1;
+        let body = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.parse_block_statement());
+        self.loops -= 1;
+        self.depth = depth;
 
         if self.peek_token_is(Semicolon) {
             self.next_token();
         }
+        body
+    }
 
-        Some(stmt)
+    fn parse_loop_control(&mut self) -> Option<Statement> {
+        let span = self.current_span;
+        if self.loops == 0 {
+            self.errors.push(ParseError { kind: ParseErrorKind::OutsideLoop(self.current_token.clone()), span });
+            return None
+        }
+        let statement = match self.current_token {
+            Token::Break => BreakStatement{ span },
+            _ => ContinueStatement{ span },
+        };
+        if self.peek_token_is(Semicolon) {
+            self.next_token();
+        }
+        Some(statement)
+    }
+
+    fn parse_assign_statement(&mut self, target: Expression, start: Span) -> Option<Statement> {
+        if !is_assignable(&target) {
+            self.errors.push(ParseError { kind: ParseErrorKind::InvalidAssignmentTarget, span: target.span() });
+            return None
+        }
+        self.next_token();
+        let operator = match self.current_token {
+            PlusAssign => Some(Plus),
+            MinusAssign => Some(Minus),
+            AsteriskAssign => Some(Asterisk),
+            SlashAssign => Some(Slash),
+            PercentAssign => Some(Percent),
+            _ => None,
+        };
+        self.next_token();
+        let value = self.parse_expression(Lowest)?;
+
+        if self.peek_token_is(Semicolon) {
+            self.next_token();
+        }
+
+        Some(AssignStatement{ target, operator, value, span: start.to(self.current_span) })
     }
 
     fn parse_expression_statement(&mut self) -> Option<Statement> {
-        let expression = self.parse_expression(Lowest);
-        match expression {
-            None => return None,
-            Some(_) => {},
+        let start = self.current_span;
+        let expression = self.parse_expression(Lowest)?;
+
+        if let Assign | PlusAssign | MinusAssign | AsteriskAssign | SlashAssign | PercentAssign = self.peek_token {
+            return self.parse_assign_statement(expression, start)
         }
-        let stmt = ExpressionStatement{ expression: expression.unwrap()};
 
         if self.peek_token_is(Semicolon) {
             self.next_token();
         }
 
-        Some(stmt)
+        Some(ExpressionStatement{ expression, span: start.to(self.current_span) })
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let depth = self.depth;
+        let expression = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.parse_nested_expression(precedence));
+        self.depth = depth;
+        expression
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        if !self.enter_nesting() {
+            return None
+        }
+
         let mut left = match self.current_token {
             Identifier(_) => self.parse_identifier(),
             Integer(_) => self.parse_integer(),
+            Float(_) => self.parse_float(),
+            Str(_) => self.parse_string(),
             True => self.parse_true(),
             False => self.parse_false(),
             Bang => self.parse_prefix(),
             Minus => self.parse_prefix(),
             LeftParenthesis => self.parse_group(),
+            LeftBracket => self.parse_array(),
+            LeftBrace => self.parse_hash(),
             If => self.parse_if(),
             Function => self.parse_function(),
-            _ => None,
-        };
+            _ => {
+                self.current_error(Expected::Expression);
+                None
+            },
+        }?;
 
         while self.current_token != Semicolon && (precedence.clone() as i32) < (self.peek_precedence() as i32) {
-            match self.peek_token {
-                Plus => { left = self.parse_infix(left.unwrap()); },
-                Minus => { left = self.parse_infix(left.unwrap()); },
-                Asterisk => { left = self.parse_infix(left.unwrap()); },
-                Slash => { left = self.parse_infix(left.unwrap()); },
-                LowerThan => { left = self.parse_infix(left.unwrap()); },
-                GreaterThan => { left = self.parse_infix(left.unwrap()); },
-                Equal => { left = self.parse_infix(left.unwrap()); },
-                NotEqual => { left = self.parse_infix(left.unwrap()); },
-                LeftParenthesis => { left = self.parse_call(left.unwrap()); },
-                _ => return left,
+            if !self.enter_nesting() {
+                return None
             }
+            left = match self.peek_token {
+                Plus => self.parse_infix(left)?,
+                Minus => self.parse_infix(left)?,
+                Asterisk => self.parse_infix(left)?,
+                Slash => self.parse_infix(left)?,
+                Percent => self.parse_infix(left)?,
+                LowerThan => self.parse_infix(left)?,
+                GreaterThan => self.parse_infix(left)?,
+                LowerThanOrEqual => self.parse_infix(left)?,
+                GreaterThanOrEqual => self.parse_infix(left)?,
+                Equal => self.parse_infix(left)?,
+                NotEqual => self.parse_infix(left)?,
+                And => self.parse_infix(left)?,
+                Or => self.parse_infix(left)?,
+                LeftParenthesis => self.parse_call(left)?,
+                LeftBracket => self.parse_index(left)?,
+                _ => return Some(left),
+            };
         }
 
-        left
+        Some(left)
     }
 
     fn parse_identifier(&self) -> Option<Expression> {
         match self.current_token {
-            Identifier(ref value) => Some(IdentifierExpression { value: value.clone() }),
+            Identifier(ref value) => Some(IdentifierExpression { value: value.clone(), span: self.current_span }),
             _ => None,
         }
     }
 
-    fn parse_integer(&self) -> Option<Expression> {
+    fn parse_integer(&mut self) -> Option<Expression> {
         match self.current_token {
-            Integer(value) => Some(IntegerExpression { value: value }),
+            Integer(ref value) => Some(IntegerExpression { value: value.clone(), span: self.current_span }),
+            _ => None,
+        }
+    }
+
+    fn parse_float(&self) -> Option<Expression> {
+        match self.current_token {
+            Float(value) => Some(FloatExpression { value, span: self.current_span }),
+            _ => None,
+        }
+    }
+
+    fn parse_string(&self) -> Option<Expression> {
+        match self.current_token {
+            Str(ref value) => Some(StringExpression { value: value.clone(), span: self.current_span }),
             _ => None,
         }
     }
 
     fn parse_true(&self) -> Option<Expression> {
-        Some(BooleanExpression { value: true })
+        Some(BooleanExpression { value: true, span: self.current_span })
     }
 
     fn parse_false(&self) -> Option<Expression> {
-        Some(BooleanExpression { value: false })
+        Some(BooleanExpression { value: false, span: self.current_span })
     }
 
     fn parse_prefix(&mut self) -> Option<Expression> {
+        let start = self.current_span;
         let prefix = self.current_token.clone();
         self.next_token();
-        match self.parse_expression(Prefix) {
-            Some(right) => {
-                let expression = PrefixExpression { prefix: prefix, right: Box::new(right) };
-                Some(expression)
-            },
-            None => None,
-        }
+        self.parse_expression(Prefix).map(|right| {
+            let span = start.to(right.span());
+            PrefixExpression { prefix, right: Box::new(right), span }
+        })
     }
 
     fn parse_infix(&mut self, left: Expression) -> Option<Expression> {
@@ -188,23 +483,25 @@ impl<'a> Parser<'a> {
         let operator = self.current_token.clone();
         let precedence = self.current_precedence();
         self.next_token();
-        let right = self.parse_expression(precedence);
-        Some(InfixExpression{ left: Box::new(left), operator: operator, right: Box::new(right.unwrap()) } )
+        let right = self.parse_expression(precedence)?;
+        let span = left.span().to(right.span());
+        Some(InfixExpression{ left: Box::new(left), operator, right: Box::new(right), span })
     }
 
     fn parse_group(&mut self) -> Option<Expression> {
         self.next_token();
 
-        let expression = self.parse_expression(Lowest);
+        let expression = self.parse_expression(Lowest)?;
 
         if self.expect_peek(RightParenthesis) {
-            expression
+            Some(expression)
         } else {
             None
         }
     }
 
     fn parse_if(&mut self) -> Option<Expression> {
+        let start = self.current_span;
 
         if !self.expect_peek(LeftParenthesis) {
             return None
@@ -212,7 +509,7 @@ impl<'a> Parser<'a> {
 
         self.next_token();
 
-        let condition = self.parse_expression(Lowest);
+        let condition = self.parse_expression(Lowest)?;
 
         if !self.expect_peek(RightParenthesis) {
             return None
@@ -222,51 +519,68 @@ impl<'a> Parser<'a> {
             return None
         }
 
-        let consequence = self.parse_block_statement();
+        let consequence = self.parse_block_statement()?;
 
-        if self.peek_token_is(Else) {
+        if !self.peek_token_is(Else) {
+            return Some(IfExpression { condition: Box::new(condition), consequence: Box::new(consequence), alternative: None, span: start.to(self.current_span) })
+        }
+        self.next_token();
+
+        let alternative = if self.peek_token_is(If) {
+            // each `else if` nests one level deeper
             self.next_token();
-            if !self.expect_peek(LeftBrace) {
+            if !self.enter_nesting() {
                 return None
-            } else {
-                let alternative = self.parse_block_statement();
-                return Some(IfExpression { condition: Box::new(condition.unwrap()), consequence: Box::new(consequence), alternative: Some(Box::new(alternative)) })
             }
-        }
-
-        Some(IfExpression { condition: Box::new(condition.unwrap()), consequence: Box::new(consequence), alternative: None })
+            let expression = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || self.parse_if())?;
+            ExpressionStatement { span: expression.span(), expression }
+        } else if self.expect_peek(LeftBrace) {
+            self.parse_block_statement()?
+        } else {
+            return None
+        };
+        Some(IfExpression { condition: Box::new(condition), consequence: Box::new(consequence), alternative: Some(Box::new(alternative)), span: start.to(self.current_span) })
     }
 
-    fn parse_block_statement(&mut self) -> Statement {
+    fn parse_block_statement(&mut self) -> Option<Statement> {
+        let start = self.current_span;
+
         self.next_token();
 
         let mut stmts = Vec::new();
 
         while self.current_token != RightBrace && self.current_token != EndOfFile {
-            if let Some(stmt) = self.parse_statement() {
-                stmts.push(stmt);
-            }
-            self.next_token();
+            self.parse_next_statement(&mut stmts);
         }
 
-        BlockStatement { statements: stmts }
+        if self.current_token == EndOfFile {
+            self.current_error(Expected::Token(RightBrace));
+            return None
+        }
+
+        Some(BlockStatement { statements: stmts, span: start.to(self.current_span) })
     }
 
     fn parse_function(&mut self) -> Option<Expression> {
+        let start = self.current_span;
 
         if !self.expect_peek(LeftParenthesis) {
             return None
         }
 
-        let parameters = self.parse_function_parameters();
+        let parameters = self.parse_function_parameters()?;
 
         if !self.expect_peek(LeftBrace) {
             return None
         }
 
+        // a function body starts outside of any loop
+        let loops = std::mem::replace(&mut self.loops, 0);
         let body = self.parse_block_statement();
+        self.loops = loops;
+        let body = body?;
 
-        Some(FunctionExpression{ parameters: parameters.unwrap(), body: Box::new(body) })
+        Some(FunctionExpression{ parameters, body: Rc::new(body), span: start.to(self.current_span) })
     }
 
     fn parse_function_parameters(&mut self) -> Option<Vec<String>> {
@@ -280,16 +594,12 @@ impl<'a> Parser<'a> {
 
         self.next_token();
 
-        if let Identifier(ref first) = self.current_token {
-            parameters.push(first.clone());
-        }
+        parameters.push(self.parse_parameter()?);
 
         while self.peek_token_is(Comma) {
             self.next_token();
             self.next_token();
-            if let Identifier(ref param) = self.current_token {
-                parameters.push(param.clone());
-            }
+            parameters.push(self.parse_parameter()?);
         }
 
         if !self.expect_peek(RightParenthesis) {
@@ -299,81 +609,170 @@ impl<'a> Parser<'a> {
         Some(parameters)
     }
 
-    fn parse_call(&mut self, left: Expression) -> Option<Expression> {
-        if let IdentifierExpression { value } = left {
-            if let Some(arguments) = self.parse_call_arguments() {
-                Some(CallExpression{ name: value, arguments: arguments } )
-            } else {
+    fn parse_parameter(&mut self) -> Option<String> {
+        match self.current_token {
+            Identifier(ref param) => Some(param.clone()),
+            _ => {
+                self.current_error(Expected::Identifier);
                 None
-            }
-        } else {
-            None
+            },
         }
     }
 
-    fn parse_call_arguments(&mut self) -> Option<Vec<Expression>> {
-        let mut arguments = Vec::new();
+    fn parse_call(&mut self, left: Expression) -> Option<Expression> {
+        let start = left.span();
+        self.next_token();
+        self.parse_expression_list(RightParenthesis).map(|arguments| CallExpression{ function: Box::new(left), arguments, span: start.to(self.current_span) })
+    }
 
-        if self.peek_token_is(RightParenthesis) {
+    fn parse_array(&mut self) -> Option<Expression> {
+        let start = self.current_span;
+        self.parse_expression_list(RightBracket).map(|elements| ArrayExpression { elements, span: start.to(self.current_span) })
+    }
+
+    /// Blocks are only parsed where `if` and `fn` expect one, so a `{` that
+    /// starts an expression always opens a hash literal.
+    fn parse_hash(&mut self) -> Option<Expression> {
+        let start = self.current_span;
+        let mut pairs = Vec::new();
+
+        while !self.peek_token_is(RightBrace) {
             self.next_token();
-            return Some(arguments)
+            let key = self.parse_expression(Lowest)?;
+            if !self.expect_peek(Colon) {
+                return None
+            }
+            self.next_token();
+            let value = self.parse_expression(Lowest)?;
+            pairs.push((key, value));
+            if !self.peek_token_is(RightBrace) && !self.expect_peek(Comma) {
+                return None
+            }
         }
+        self.next_token();
+
+        Some(HashExpression { pairs, span: start.to(self.current_span) })
+    }
 
+    fn parse_index(&mut self, left: Expression) -> Option<Expression> {
         self.next_token();
         self.next_token();
-
-        if let Some(first) = self.parse_expression(Lowest) {
-            arguments.push(first);
+        let index = self.parse_expression(Lowest)?;
+        if !self.expect_peek(RightBracket) {
+            return None
         }
+        let span = left.span().to(self.current_span);
+        Some(IndexExpression { left: Box::new(left), index: Box::new(index), span })
+    }
 
-        while self.peek_token_is(Comma) {
+    /// Parses the comma separated expressions following the opening
+    /// delimiter in the current token, up to the closing `end`.
+    fn parse_expression_list(&mut self, end: Token) -> Option<Vec<Expression>> {
+        let mut arguments = Vec::new();
+
+        if self.peek_token_is(end.clone()) {
             self.next_token();
+            return Some(arguments)
+        }
+
+        loop {
             self.next_token();
-            if let Some(arg) = self.parse_expression(Lowest) {
-                arguments.push(arg);
+            match self.parse_expression(Lowest) {
+                Some(arg) => arguments.push(arg),
+                // nothing between two delimiters, e.g. `add(1, , 2)` or `add(1, )`
+                None if self.current_token == Comma => continue,
+                None if self.current_token == end => return Some(arguments),
+                None => self.skip_argument(),
+            }
+            if !self.peek_token_is(Comma) {
+                break
             }
+            self.next_token();
         }
 
-        if !self.expect_peek(RightParenthesis) {
+        if !self.expect_peek(end) {
             return None
         }
 
         Some(arguments)
     }
 
+    /// Skips the rest of a list element that failed to parse, stopping
+    /// before the `,`, `)` or `]` that ends it.
+    fn skip_argument(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.peek_token {
+                EndOfFile => return,
+                Comma | RightParenthesis | RightBracket | Semicolon | RightBrace if depth == 0 => return,
+                LeftParenthesis | LeftBrace | LeftBracket => depth += 1,
+                RightParenthesis | RightBrace | RightBracket => depth -= 1,
+                _ => {},
+            }
+            self.next_token();
+        }
+    }
+
     fn peek_precedence(&self) -> Precedence {
         match self.peek_token {
+            Or => LogicalOr,
+            And => LogicalAnd,
             Equal | NotEqual => Equals,
-            LowerThan | GreaterThan => LessGreater,
+            LowerThan | GreaterThan | LowerThanOrEqual | GreaterThanOrEqual => LessGreater,
             Plus | Minus => Sum,
-            Slash | Asterisk => Product,
+            Slash | Asterisk | Percent => Product,
             LeftParenthesis => Call,
+            LeftBracket => Index,
             _ => Lowest,
         }
     }
 
     fn current_precedence(&self) -> Precedence {
         match self.current_token {
+            Or => LogicalOr,
+            And => LogicalAnd,
             Equal | NotEqual => Equals,
-            LowerThan | GreaterThan => LessGreater,
+            LowerThan | GreaterThan | LowerThanOrEqual | GreaterThanOrEqual => LessGreater,
             Plus | Minus => Sum,
-            Slash | Asterisk => Product,
+            Slash | Asterisk | Percent => Product,
             LeftParenthesis => Call,
+            LeftBracket => Index,
             _ => Lowest,
         }
     }
 
 }
 
+fn is_assignable(target: &Expression) -> bool {
+    match *target {
+        IdentifierExpression{ .. } => true,
+        IndexExpression{ ref left, .. } => is_assignable(left),
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Precedence {
     Lowest,
+    LogicalOr,
+    LogicalAnd,
     Equals,
     LessGreater,
     Sum,
     Product,
     Prefix,
     Call,
+    Index,
+}
+
+#[cfg(test)]
+fn is_integer(expression: &Expression, expected: i64) -> bool {
+    matches!(*expression, IntegerExpression{ ref value, .. } if *value == expected.into())
+}
+
+#[cfg(test)]
+fn is_identifier(expression: &Expression, expected: &str) -> bool {
+    matches!(*expression, IdentifierExpression{ ref value, .. } if value == expected)
 }
 
 #[test]
@@ -385,11 +784,10 @@ fn let_statement_test() {
         let foobar = 838383;
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
-    println!("{:?}", program);
-    assert_eq!(LetStatement{ name: "x".to_string(), value: IntegerExpression{ value: 5 } }, program.statements()[0]);
-    assert_eq!(LetStatement{ name: "y".to_string(), value: IntegerExpression{ value: 10 } }, program.statements()[1]);
-    assert_eq!(LetStatement{ name: "foobar".to_string(), value: IntegerExpression{ value: 838383 } }, program.statements()[2]);
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.statements()[0], LetStatement{ ref name, ref value, .. } if name == "x" && is_integer(value, 5)));
+    assert!(matches!(program.statements()[1], LetStatement{ ref name, ref value, .. } if name == "y" && is_integer(value, 10)));
+    assert!(matches!(program.statements()[2], LetStatement{ ref name, ref value, .. } if name == "foobar" && is_integer(value, 838383)));
 }
 
 #[test]
@@ -401,11 +799,10 @@ fn return_statement_test() {
         return 993322;
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
-    println!("{:?}", program);
-    assert_eq!(ReturnStatement{ value: IntegerExpression{ value: 5 } }, program.statements()[0]);
-    assert_eq!(ReturnStatement{ value: IntegerExpression{ value: 10 } }, program.statements()[1]);
-    assert_eq!(ReturnStatement{ value: IntegerExpression{ value: 993322 } }, program.statements()[2]);
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.statements()[0], ReturnStatement{ ref value, .. } if is_integer(value, 5)));
+    assert!(matches!(program.statements()[1], ReturnStatement{ ref value, .. } if is_integer(value, 10)));
+    assert!(matches!(program.statements()[2], ReturnStatement{ ref value, .. } if is_integer(value, 993322)));
 }
 
 #[test]
@@ -427,20 +824,19 @@ fn expression_statement_test() {
     ");
 
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
-    println!("{:?}", program);
-    assert_eq!(ExpressionStatement{ expression: IdentifierExpression{ value: "foobar".to_string() } }, program.statements()[0]);
-    assert_eq!(ExpressionStatement{ expression: IntegerExpression{ value: 5 } }, program.statements()[1]);
-    assert_eq!(ExpressionStatement{ expression: PrefixExpression{ prefix: Bang, right: Box::new(IntegerExpression{ value: 5 }) } }, program.statements()[2]);
-    assert_eq!(ExpressionStatement{ expression: PrefixExpression{ prefix: Minus, right: Box::new(IntegerExpression{ value: 15 }) } }, program.statements()[3]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: Plus, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[4]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: Minus, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[5]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: Asterisk, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[6]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: Slash, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[7]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: LowerThan, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[8]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: GreaterThan, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[9]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: Equal, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[10]);
-    assert_eq!(ExpressionStatement{ expression: InfixExpression{ left: Box::new(IntegerExpression{ value: 5 }), operator: NotEqual, right: Box::new(IntegerExpression{ value: 6 }) } }, program.statements()[11]);
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.statements()[0], ExpressionStatement{ expression: IdentifierExpression{ ref value, .. }, .. } if value == "foobar"));
+    assert!(matches!(program.statements()[1], ExpressionStatement{ expression: ref value, .. } if is_integer(value, 5)));
+    assert!(matches!(program.statements()[2], ExpressionStatement{ expression: PrefixExpression{ prefix: Bang, ref right, .. }, .. } if is_integer(right, 5)));
+    assert!(matches!(program.statements()[3], ExpressionStatement{ expression: PrefixExpression{ prefix: Minus, ref right, .. }, .. } if is_integer(right, 15)));
+    assert!(matches!(program.statements()[4], ExpressionStatement{ expression: InfixExpression{ ref left, operator: Plus, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
+    assert!(matches!(program.statements()[5], ExpressionStatement{ expression: InfixExpression{ ref left, operator: Minus, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
+    assert!(matches!(program.statements()[6], ExpressionStatement{ expression: InfixExpression{ ref left, operator: Asterisk, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
+    assert!(matches!(program.statements()[7], ExpressionStatement{ expression: InfixExpression{ ref left, operator: Slash, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
+    assert!(matches!(program.statements()[8], ExpressionStatement{ expression: InfixExpression{ ref left, operator: LowerThan, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
+    assert!(matches!(program.statements()[9], ExpressionStatement{ expression: InfixExpression{ ref left, operator: GreaterThan, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
+    assert!(matches!(program.statements()[10], ExpressionStatement{ expression: InfixExpression{ ref left, operator: Equal, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
+    assert!(matches!(program.statements()[11], ExpressionStatement{ expression: InfixExpression{ ref left, operator: NotEqual, ref right, .. }, .. } if is_integer(left, 5) && is_integer(right, 6)));
 }
 
 #[test]
@@ -462,7 +858,7 @@ fn parse_operator_precedence_test() {
         3 + 4; -5 * 5;
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
+    let program = parser.parse_program().unwrap();
     assert_eq!("((-a) * b)", program.statements()[0].to_string());
     assert_eq!("(!(-a))", program.statements()[1].to_string());
     assert_eq!("((a + b) + c)", program.statements()[2].to_string());
@@ -480,6 +876,23 @@ fn parse_operator_precedence_test() {
     assert_eq!("((-5) * 5)", program.statements()[13].to_string());
 }
 
+#[test]
+fn parse_logical_operator_test() {
+    let cases = vec![
+        ("a || b && c", "(a || (b && c))"),
+        ("a && b || c && d", "((a && b) || (c && d))"),
+        ("a == b && c != d", "((a == b) && (c != d))"),
+        ("a <= b || a >= c", "((a <= b) || (a >= c))"),
+        ("!a && b", "((!a) && b)"),
+        ("a + b % c * d", "(a + ((b % c) * d))"),
+        ("x %= 2", "x %= 2"),
+    ];
+    for (input, expected) in cases {
+        let mut parser = Parser::new(Lexer::new(input));
+        assert_eq!(expected, parser.parse_program().unwrap().to_string());
+    }
+}
+
 #[test]
 #[ignore]
 fn parse_operator_boolean_test() {
@@ -490,7 +903,7 @@ fn parse_operator_boolean_test() {
         3 < 5 == true;
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
+    let program = parser.parse_program().unwrap();
     assert_eq!("true", program.statements()[0].to_string());
     assert_eq!("false", program.statements()[1].to_string());
     assert_eq!("((3 > 5) == false)", program.statements()[2].to_string());
@@ -508,7 +921,7 @@ fn parse_grouped_expressions_test() {
         !(true == true);
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
+    let program = parser.parse_program().unwrap();
     assert_eq!("((1 + (2 + 3)) + 4)", program.statements()[0].to_string());
     assert_eq!("((5 + 5) * 2)", program.statements()[1].to_string());
     assert_eq!("(2 / (5 + 5))", program.statements()[2].to_string());
@@ -523,31 +936,111 @@ fn parse_if_test() {
         if (x < y) { x } else { y }
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
-    // println!("{:?}", program.statements()[0]);
+    let program = parser.parse_program().unwrap();
 
-    if let ExpressionStatement {ref expression} = program.statements()[0] {
+    if let ExpressionStatement {ref expression, ..} = program.statements()[0] {
         match *expression {
-            IfExpression{ ref condition, ref consequence, ref alternative } => {
-                assert_eq!(Box::new(InfixExpression{ left: Box::new(IdentifierExpression{value: "x".to_string()}), operator: LowerThan, right: Box::new(IdentifierExpression{value: "y".to_string()})}), *condition);
-                match *consequence {
-                    _ => {},
-                }
-                assert_eq!( Box::new(BlockStatement{ statements: vec![ExpressionStatement{ expression: IdentifierExpression{ value: "x".to_string() }}] }), *consequence);
+            IfExpression{ ref condition, ref consequence, ref alternative, .. } => {
+                assert!(matches!(**condition, InfixExpression{ ref left, operator: LowerThan, ref right, .. } if is_identifier(left, "x") && is_identifier(right, "y")));
+                assert!(matches!(**consequence, BlockStatement{ ref statements, .. } if matches!(statements[..], [ExpressionStatement{ ref expression, .. }] if is_identifier(expression, "x"))));
                 match *alternative {
                     Some(ref alt) => {
-                        assert_eq!( Box::new(BlockStatement{ statements: vec![ExpressionStatement{ expression: IdentifierExpression{ value: "y".to_string() }}] }), *alt);
+                        assert!(matches!(**alt, BlockStatement{ ref statements, .. } if matches!(statements[..], [ExpressionStatement{ ref expression, .. }] if is_identifier(expression, "y"))));
                     },
-                    None => assert!(false),
+                    None => panic!(),
                 }
             },
-            _ => assert!(false),
+            _ => panic!(),
         }
     } else {
-        assert!(false);
+        panic!();
     }
 }
 
+#[test]
+fn parse_else_if_test() {
+    let mut parser = Parser::new(Lexer::new("if (a) { 1 } else if (b) { 2 } else if (c) { 3 } else { 4 }"));
+    let program = parser.parse_program().unwrap();
+    match program.statements()[0] {
+        ExpressionStatement{ expression: IfExpression{ alternative: Some(ref alternative), .. }, .. } => {
+            assert!(matches!(**alternative, ExpressionStatement{ expression: IfExpression{ ref condition, alternative: Some(_), .. }, .. } if is_identifier(condition, "b")));
+            assert_eq!((1, 60), (alternative.span().end.line, alternative.span().end.column));
+        },
+        _ => panic!(),
+    }
+
+    let cases = vec![
+        ("if (x < y) { x }", "if ((x < y)) { x;  }"),
+        ("if (x) { x } else { y }", "if (x) { x;  } else { y;  }"),
+        ("if (a) { 1 } else if (b) { 2 }", "if (a) { 1;  } else if (b) { 2;  }"),
+        ("if (a) { 1 } else if (b) { 2 } else { let c = 3; c }", "if (a) { 1;  } else if (b) { 2;  } else { let c = 3; c;  }"),
+        ("1 + if (a) { if (b) { 2 } } else { return 3; }", "(1 + if (a) { if (b) { 2;  };  } else { return 3;  })"),
+    ];
+    for (input, expected) in cases {
+        let printed = Parser::new(Lexer::new(input)).parse_program().unwrap().to_string();
+        assert_eq!(expected, printed);
+        let reprinted = Parser::new(Lexer::new(&printed)).parse_program().unwrap().to_string();
+        assert_eq!(printed, reprinted);
+    }
+
+    assert!(Parser::new(Lexer::new("if (a) { 1 } else if { 2 }")).parse_program().is_err());
+    let ladder = format!("{} {{ 0 }}", vec!["if (a) { 1 }"; MAX_NESTING + 1].join(" else "));
+    let errors = Parser::new(Lexer::new(&ladder)).parse_program().unwrap_err();
+    assert_eq!(ParseErrorKind::TooDeeplyNested, errors[0].kind);
+}
+
+#[test]
+fn parse_assign_test() {
+    let mut parser = Parser::new(Lexer::new("x += 1;"));
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.statements()[0], AssignStatement{ ref target, operator: Some(Plus), ref value, .. } if is_identifier(target, "x") && is_integer(value, 1)));
+    let span = program.statements()[0].span();
+    assert_eq!((1, 1, 1, 8), (span.start.line, span.start.column, span.end.line, span.end.column));
+
+    let cases = vec![
+        ("x = y + 1", "x = (y + 1)"),
+        ("a[0][i] -= 2;", "((a[0])[i]) -= 2"),
+        ("(x) *= 2", "x *= 2"),
+        ("h[\"k\"] /= 2", "(h[\"k\"]) /= 2"),
+        ("while (true) { n = n - 1; }", "while (true) { n = (n - 1);  }"),
+    ];
+    for (input, expected) in cases {
+        let printed = Parser::new(Lexer::new(input)).parse_program().unwrap().to_string();
+        assert_eq!(expected, printed);
+        let reprinted = Parser::new(Lexer::new(&printed)).parse_program().unwrap().to_string();
+        assert_eq!(printed, reprinted);
+    }
+}
+
+#[test]
+fn parse_loop_test() {
+    let mut parser = Parser::new(Lexer::new("for (x in [1, 2]) { if (x > 1) { break; } continue; }"));
+    let program = parser.parse_program().unwrap();
+    match program.statements()[0] {
+        ForStatement{ ref variable, iterable: ArrayExpression{ .. }, ref body, .. } => {
+            assert_eq!("x", variable);
+            assert!(matches!(**body, BlockStatement{ ref statements, .. } if matches!(statements[1], ContinueStatement{ .. })));
+        },
+        _ => panic!(),
+    }
+
+    let cases = vec![
+        ("while (i < 3) { let i = i + 1; }", "while ((i < 3)) { let i = (i + 1);  }"),
+        ("for (k in {1: 2}) { puts(k) };", "for (k in {1: 2}) { puts(k);  }"),
+        ("while (true) { for (x in xs) { continue } break }", "while (true) { for (x in xs) { continue;  }; break;  }"),
+        ("let f = fn() { while (true) { return 1; } };", "let f = fn() { while (true) { return 1;  };  }"),
+    ];
+    for (input, expected) in cases {
+        let printed = Parser::new(Lexer::new(input)).parse_program().unwrap().to_string();
+        assert_eq!(expected, printed);
+        let reprinted = Parser::new(Lexer::new(&printed)).parse_program().unwrap().to_string();
+        assert_eq!(printed, reprinted);
+    }
+
+    let nested = format!("{}{}", "while (true) { ".repeat(100_000), "}".repeat(100_000));
+    let errors = Parser::new(Lexer::new(&nested)).parse_program().unwrap_err();
+    assert_eq!(ParseErrorKind::TooDeeplyNested, errors[0].kind);
+}
 
 #[test]
 #[ignore]
@@ -556,20 +1049,18 @@ fn parse_function_expressions_test() {
         fn(x, y) { x + y; }
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
-    // println!("{}", program.statements()[0]);
-    // println!("{:?}", program.statements()[0]);
-    if let ExpressionStatement {ref expression} = program.statements()[0] {
+    let program = parser.parse_program().unwrap();
+    if let ExpressionStatement {ref expression, ..} = program.statements()[0] {
         match *expression {
-            FunctionExpression{ ref parameters, body: _ } => {
+            FunctionExpression{ ref parameters, .. } => {
                 assert_eq!(2, parameters.len());
                 assert_eq!("x".to_string(), parameters[0]);
                 assert_eq!("y".to_string(), parameters[1]);
             },
-            _ => assert!(false),
+            _ => panic!(),
         }
     } else {
-        assert!(false);
+        panic!();
     }
 }
 
@@ -583,20 +1074,18 @@ fn parse_call_expressions_test() {
         add(a + b + c * d / f + g);
     ");
     let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
-    // println!("{:?}", program.statements()[0]);
-    // println!("{}", program.statements()[0]);
+    let program = parser.parse_program().unwrap();
 
-    if let ExpressionStatement {ref expression} = program.statements()[0] {
+    if let ExpressionStatement {ref expression, ..} = program.statements()[0] {
         match *expression {
-            CallExpression{ ref name, ref arguments } => {
-                assert_eq!("add".to_string(), *name);
+            CallExpression{ ref function, ref arguments, .. } => {
+                assert!(is_identifier(function, "add"));
                 assert_eq!(3, arguments.len());
             },
-            _ => assert!(false),
+            _ => panic!(),
         }
     } else {
-        assert!(false);
+        panic!();
     }
 
 
@@ -604,3 +1093,205 @@ fn parse_call_expressions_test() {
     assert_eq!("add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))", program.statements()[2].to_string());
     assert_eq!("add((((a + b) + ((c * d) / f)) + g))", program.statements()[3].to_string());
 }
+
+#[test]
+fn parse_call_callee_test() {
+    let cases = vec![
+        ("adder(1)(2)", "adder(1)(2)"),
+        ("fns[0](x)", "(fns[0])(x)"),
+        ("(fn(x) { x })(5)", "fn(x) { x;  }(5)"),
+        ("-f(1)", "(-f(1))"),
+        ("a + b(c)(d)", "(a + b(c)(d))"),
+    ];
+    for (input, expected) in cases {
+        let mut parser = Parser::new(Lexer::new(input));
+        assert_eq!(expected, parser.parse_program().unwrap().to_string());
+    }
+
+    let mut parser = Parser::new(Lexer::new("adder(1)(2)"));
+    let program = parser.parse_program().unwrap();
+    let span = program.statements()[0].span();
+    assert_eq!((1, 12), (span.end.line, span.end.column));
+}
+
+#[test]
+fn parse_span_test() {
+    let lexer = Lexer::new("let x = 1 + 2;\nadd(x, 3)");
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse_program().unwrap();
+
+    let let_span = program.statements()[0].span();
+    assert_eq!((0, 14), (let_span.start.offset, let_span.end.offset));
+    if let LetStatement{ ref value, .. } = program.statements()[0] {
+        assert_eq!((8, 13), (value.span().start.offset, value.span().end.offset));
+    } else {
+        panic!();
+    }
+
+    let call_span = program.statements()[1].span();
+    assert_eq!((2, 1), (call_span.start.line, call_span.start.column));
+    assert_eq!((2, 10), (call_span.end.line, call_span.end.column));
+}
+
+#[test]
+fn parse_literal_test() {
+    let mut parser = Parser::new(Lexer::new("\"a\\tb\" + \"\\u{e9}\"; 1.5 * 2e0;"));
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.statements()[0], ExpressionStatement{ expression: InfixExpression{ ref left, .. }, .. } if matches!(**left, StringExpression{ ref value, .. } if value == "a\tb")));
+    assert_eq!("(\"a\\tb\" + \"é\")", program.statements()[0].to_string());
+    assert_eq!("(1.5 * 2.0)", program.statements()[1].to_string());
+}
+
+#[test]
+fn parse_array_test() {
+    let mut parser = Parser::new(Lexer::new("[1, 2 * 2, \"a\"]; []; a * [1, 2][b + 1]; add(a)[0]; -a[1];"));
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.statements()[0], ExpressionStatement{ expression: ArrayExpression{ ref elements, .. }, .. } if elements.len() == 3 && is_integer(&elements[0], 1)));
+    assert_eq!("[1, (2 * 2), \"a\"]", program.statements()[0].to_string());
+    assert_eq!("[]", program.statements()[1].to_string());
+    assert_eq!("(a * ([1, 2][(b + 1)]))", program.statements()[2].to_string());
+    assert_eq!("(add(a)[0])", program.statements()[3].to_string());
+    assert_eq!("(-(a[1]))", program.statements()[4].to_string());
+
+    let span = program.statements()[2].span();
+    assert_eq!((21, 39), (span.start.offset, span.end.offset));
+}
+
+#[test]
+fn parse_hash_test() {
+    let mut parser = Parser::new(Lexer::new("{\"one\": 1, 2: 1 + 1, true: [3],}; {}; if (x) { {1: 2} } else { {} }; {\"a\": 1}[\"a\"]"));
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.statements()[0], ExpressionStatement{ expression: HashExpression{ ref pairs, .. }, .. } if pairs.len() == 3 && is_integer(&pairs[0].1, 1)));
+    assert_eq!("{\"one\": 1, 2: (1 + 1), true: [3]}", program.statements()[0].to_string());
+    assert_eq!("{}", program.statements()[1].to_string());
+    assert!(matches!(program.statements()[2], ExpressionStatement{ expression: IfExpression{ ref consequence, .. }, .. } if matches!(**consequence, BlockStatement{ ref statements, .. } if matches!(statements[..], [ExpressionStatement{ expression: HashExpression{ .. }, .. }]))));
+    assert_eq!("({\"a\": 1}[\"a\"])", program.statements()[3].to_string());
+}
+
+#[test]
+fn parse_errors_test() {
+    let cases = vec![
+        ("let = 5;", "expected identifier, found `=`", (1, 5)),
+        ("let x 5;", "expected `=`, found `5`", (1, 7)),
+        ("if (x { x }", "expected `)`, found `{`", (1, 7)),
+        ("fn(x, 1) { x }", "expected identifier, found `1`", (1, 7)),
+        ("add(1, 2", "expected `)`, found end of input", (1, 9)),
+        ("\n  )", "expected expression, found `)`", (2, 3)),
+        ("@", "unexpected character `@`", (1, 1)),
+        ("fn(x) { x", "expected `}`, found end of input", (1, 10)),
+        ("let s = \"abc;", "unterminated string literal", (1, 9)),
+        ("[1, 2", "expected `]`, found end of input", (1, 6)),
+        ("a[1", "expected `]`, found end of input", (1, 4)),
+        ("{1 2}", "expected `:`, found `2`", (1, 4)),
+        ("{1: 2 3: 4}", "expected `,`, found `3`", (1, 7)),
+        ("break;", "`break` outside of a loop", (1, 1)),
+        ("while (true) { fn() { continue } }", "`continue` outside of a loop", (1, 23)),
+        ("for (1 in a) {}", "expected identifier, found `1`", (1, 6)),
+        ("for (x a) {}", "expected `in`, found `a`", (1, 8)),
+        ("while true {}", "expected `(`, found `true`", (1, 7)),
+        ("let x = 1; x + 1 = 2;", "invalid assignment target", (1, 12)),
+        ("f()[0] = 1", "invalid assignment target", (1, 1)),
+        ("x += ;", "expected expression, found `;`", (1, 6)),
+        ("a & b", "unexpected character `&`", (1, 3)),
+        ("a ||", "expected expression, found end of input", (1, 5)),
+    ];
+    for (input, message, (line, column)) in cases {
+        let mut parser = Parser::new(Lexer::new(input));
+        let errors = parser.parse_program().unwrap_err();
+        assert_eq!(message, errors[0].to_string());
+        assert_eq!((line, column), (errors[0].span.start.line, errors[0].span.start.column));
+    }
+}
+
+#[test]
+fn parse_errors_collected_test() {
+    let mut parser = Parser::new(Lexer::new("let x 1; let = 2; let z = 3;"));
+    let errors = parser.parse_program().unwrap_err();
+    assert_eq!(ParseErrorKind::Unexpected{ expected: Expected::Token(Assign), found: Integer(1.into()) }, errors[0].kind);
+    assert_eq!(ParseErrorKind::Unexpected{ expected: Expected::Identifier, found: Assign }, errors[1].kind);
+}
+
+#[test]
+fn parse_empty_statement_test() {
+    let mut parser = Parser::new(Lexer::new("let x = 1;; x;"));
+    assert_eq!(2, parser.parse_program().unwrap().statements().len());
+}
+
+#[test]
+fn parse_malformed_input_test() {
+    let inputs = vec![
+        "let x = ;", "let x =", "let", "return", "return ;", "1 +", "1 + ;", "-", "!", "(", "(1", ")",
+        "if", "if (", "if (x", "if (x)", "if (x) {", "if (x) { 1 } else", "if (x) { 1 } else {",
+        "fn", "fn(", "fn(x", "fn(x,", "fn(x) ", "fn(x) {", "add(", "add(1,", "add(1, 2", "f(1)(",
+        "{", "}", "let x = 1 +;", "😀", "٣",
+    ];
+    for input in inputs {
+        let mut parser = Parser::new(Lexer::new(input));
+        assert!(parser.parse_program().is_err(), "{:?} should not parse", input);
+    }
+}
+
+#[test]
+fn parse_nesting_limit_test() {
+    let nested = |n: usize| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+    assert!(Parser::new(Lexer::new(&nested(MAX_NESTING - 1))).parse_program().is_ok());
+    let input = nested(100_000);
+    let errors = Parser::new(Lexer::new(&input)).parse_program().unwrap_err();
+    assert_eq!(ParseErrorKind::TooDeeplyNested, errors[0].kind);
+
+    // long flat chains are not pathological, only very long ones are
+    let sum = vec!["1"; 130].join(" + ");
+    assert!(Parser::new(Lexer::new(&sum)).parse_program().is_ok());
+    let concat = format!("let s = \"\"{};", " + \"x\"".repeat(130));
+    assert!(Parser::new(Lexer::new(&concat)).parse_program().is_ok());
+    let ladder = format!("{} else {{ 0 }}", vec!["if (a) { 1 }"; 130].join(" else "));
+    assert!(Parser::new(Lexer::new(&ladder)).parse_program().is_ok());
+
+    let sum = vec!["1"; 100_000].join(" + ");
+    let errors = Parser::new(Lexer::new(&sum)).parse_program().unwrap_err();
+    assert_eq!(ParseErrorKind::TooDeeplyNested, errors[0].kind);
+}
+
+#[test]
+fn parse_loop_recovery_test() {
+    let mut parser = Parser::new(Lexer::new("let x 5\nwhile (true) { break; }\nlet y = 1;\nlet = 2 for (i in xs) { i }"));
+    let (program, errors) = parser.parse_program_partial();
+    assert_eq!(2, errors.len());
+    let statements: Vec<String> = program.statements().iter().map(|s| s.to_string()).collect();
+    assert_eq!(vec!["while (true) { break;  }", "let y = 1", "for (i in xs) { i;  }"], statements);
+}
+
+#[test]
+fn parse_recovery_test() {
+    let input = "
+        let x 5;
+        let y = 10;
+        let = 1;
+        let f = fn(a) { let = a; a * 2 };
+        } 3;
+        add(1, +, 3, , 4);
+        let z = (1 + ;
+        z;
+    ";
+    let mut parser = Parser::new(Lexer::new(input));
+    let (program, errors) = parser.parse_program_partial();
+    let messages: Vec<String> = errors.iter().map(|e| format!("{}: {}", e.span, e)).collect();
+    assert_eq!(vec![
+        "2:15: expected `=`, found `5`",
+        "4:13: expected identifier, found `=`",
+        "5:29: expected identifier, found `=`",
+        "6:9: expected expression, found `}`",
+        "7:16: expected expression, found `+`",
+        "7:22: expected expression, found `,`",
+        "8:22: expected expression, found `;`",
+    ], messages);
+
+    let statements: Vec<String> = program.statements().iter().map(|s| s.to_string()).collect();
+    assert_eq!(vec![
+        "let y = 10",
+        "let f = fn(a) { (a * 2);  }",
+        "3",
+        "add(1, 3, 4)",
+        "z",
+    ], statements);
+}
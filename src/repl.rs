@@ -0,0 +1,387 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::Context;
+use rustyline::Editor;
+use rustyline::Helper;
+
+use interpreter::diagnostics::Diagnostic;
+use interpreter::environment::Env;
+use interpreter::environment::Environment;
+use interpreter::evaluator;
+use interpreter::builtins::BUILTINS;
+use interpreter::lexer::Lexer;
+use interpreter::object::Object;
+use interpreter::parser::ParseError;
+use interpreter::parser::Parser;
+use interpreter::token::Position;
+use interpreter::token::Span;
+use interpreter::token::Token;
+use interpreter::token::KEYWORDS;
+
+use Reporter;
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+const SOURCE_NAME: &str = "<repl>";
+const HISTORY_FILE: &str = ".interpreter_history";
+
+const COMMANDS: &[&str] = &["tokens", "ast", "env", "load", "reset", "time", "help"];
+
+const HELP: &str = "\
+:tokens <code>  print the tokens of <code>
+:ast <code>     print the syntax tree of <code>
+:env            list the current bindings
+:load <file>    evaluate <file> into the session
+:reset          drop all bindings
+:time <code>    evaluate <code> and report how long it took
+:help           show this message";
+
+/// The state kept between lines of a REPL session.
+pub struct Session {
+    env: Env,
+    /// Every input evaluated so far, in order. Each is lexed at the offset
+    /// where the previous one ends, so the spans in a function defined by one
+    /// input can still be traced back to it when a later input calls it.
+    inputs: Vec<Input>,
+}
+
+struct Input {
+    offset: usize,
+    file: String,
+    source: String,
+}
+
+impl Session {
+
+    pub fn new() -> Self {
+        Session { env: Environment::new(), inputs: Vec::new() }
+    }
+
+    /// Evaluates `source` against the session environment. Errors are
+    /// reported against the input they come from, by default `file`, and
+    /// leave any bindings made before them in place.
+    pub fn eval(&mut self, file: &str, source: &str, reporter: &Reporter) -> Option<Object> {
+        let offset = self.inputs.last().map_or(0, |input| input.offset + input.source.len() + 1);
+        let program = match Parser::new(Lexer::with_offset(source, offset)).parse_program() {
+            Ok(program) => program,
+            Err(errors) => {
+                for error in &errors {
+                    let error = ParseError { span: rebase(error.span, offset), ..error.clone() };
+                    reporter.report(file, source, &Diagnostic::from(&error));
+                }
+                return None
+            },
+        };
+        self.inputs.push(Input { offset, file: file.to_string(), source: source.to_string() });
+        match evaluator::eval_program(&program, &self.env) {
+            Ok(value) => Some(value),
+            Err(mut error) => {
+                let input = self.locate(error.span);
+                error.span = rebase(error.span, input.offset);
+                reporter.report(&input.file, &input.source, &Diagnostic::from(&error));
+                None
+            },
+        }
+    }
+
+    /// The input that `span` was lexed from.
+    fn locate(&self, span: Span) -> &Input {
+        let index = self.inputs.partition_point(|input| input.offset <= span.start.offset);
+        &self.inputs[index.saturating_sub(1)]
+    }
+
+    /// Runs one complete piece of input, either code or a colon-command,
+    /// writing what it prints to `out`.
+    pub fn execute<W: Write>(&mut self, input: &str, reporter: &Reporter, out: &mut W) -> io::Result<()> {
+        let trimmed = input.trim();
+        if !trimmed.starts_with(':') {
+            let value = self.eval(SOURCE_NAME, input, reporter);
+            return print_value(value, out)
+        }
+
+        let (command, argument) = match trimmed.find(char::is_whitespace) {
+            Some(index) => (&trimmed[..index], trimmed[index..].trim_start()),
+            None => (trimmed, ""),
+        };
+        match command {
+            ":tokens" => print_tokens(argument, out),
+            ":ast" => match Parser::new(Lexer::new(argument)).parse_program() {
+                Ok(program) => writeln!(out, "{}\n{:#?}", program, program),
+                Err(errors) => {
+                    for error in &errors {
+                        reporter.report(SOURCE_NAME, argument, &Diagnostic::from(error));
+                    }
+                    Ok(())
+                },
+            },
+            ":env" => {
+                for (name, value) in self.env.borrow().bindings() {
+                    writeln!(out, "{} = {}", name, value.inspect())?;
+                }
+                Ok(())
+            },
+            ":load" => {
+                match fs::read_to_string(argument) {
+                    Ok(source) => { self.eval(argument, &source, reporter); },
+                    Err(error) => eprintln!("error: cannot read `{}`: {}", argument, error),
+                }
+                Ok(())
+            },
+            ":reset" => {
+                self.env = Environment::new();
+                Ok(())
+            },
+            ":time" => {
+                let start = Instant::now();
+                let value = self.eval(SOURCE_NAME, argument, reporter);
+                let elapsed = start.elapsed();
+                print_value(value, out)?;
+                writeln!(out, "time: {:?}", elapsed)
+            },
+            ":help" => writeln!(out, "{}", HELP),
+            _ => {
+                eprintln!("error: unknown command `{}`, try `:help`", command);
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Makes the offsets in `span` relative to an input lexed at `offset`.
+fn rebase(span: Span, offset: usize) -> Span {
+    let rebase = |position: Position| Position { offset: position.offset.saturating_sub(offset), ..position };
+    Span::new(rebase(span.start), rebase(span.end))
+}
+
+fn print_value<W: Write>(value: Option<Object>, out: &mut W) -> io::Result<()> {
+    match value {
+        Some(Object::Null) | None => Ok(()),
+        Some(value) => writeln!(out, "{}", value.inspect()),
+    }
+}
+
+fn print_tokens<W: Write>(source: &str, out: &mut W) -> io::Result<()> {
+    let mut lexer = Lexer::new(source);
+    loop {
+        let spanned = lexer.next_spanned_token();
+        match lexer.take_error() {
+            Some(error) => writeln!(out, "{} {:?} ({})", spanned.span, spanned.token, error)?,
+            None => writeln!(out, "{} {:?}", spanned.span, spanned.token)?,
+        }
+        if spanned.token == Token::EndOfFile {
+            return Ok(())
+        }
+    }
+}
+
+/// Whether `source` closes every `(`, `{` and `[` it opens. Unbalanced closers
+/// count as complete so the parser gets to report them.
+fn is_complete(source: &str) -> bool {
+    let mut lexer = Lexer::new(source);
+    let mut depth: usize = 0;
+    loop {
+        match lexer.next_token() {
+            Token::LeftParenthesis | Token::LeftBrace | Token::LeftBracket => depth += 1,
+            Token::RightParenthesis | Token::RightBrace | Token::RightBracket => depth = depth.saturating_sub(1),
+            Token::EndOfFile => return depth == 0,
+            _ => {},
+        }
+    }
+}
+
+/// Completes keywords, builtins, colon-commands and the names bound in the
+/// session.
+struct Completion {
+    names: Vec<String>,
+}
+
+impl Completion {
+
+    /// Where the word under `pos` starts and the candidates that extend it.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos].char_indices().rev()
+            .take_while(|&(_, c)| c.is_alphabetic())
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let word = &line[start..pos];
+        if word.is_empty() {
+            return (pos, Vec::new())
+        }
+
+        let words: Vec<&str> = if line[..start].trim_start() == ":" {
+            COMMANDS.to_vec()
+        } else {
+            KEYWORDS.iter().cloned()
+                .chain(BUILTINS.iter().map(|b| b.name))
+                .chain(self.names.iter().map(String::as_str))
+                .collect()
+        };
+        let mut candidates: Vec<String> = words.into_iter()
+            .filter(|w| w.starts_with(word))
+            .map(String::from)
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        (start, candidates)
+    }
+}
+
+impl Completer for Completion {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = String;
+}
+
+impl Highlighter for Completion {}
+
+impl Validator for Completion {}
+
+impl Helper for Completion {}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Reads input until end of file, executing each complete chunk. Lines are
+/// buffered behind a continuation prompt until their brackets balance.
+pub fn run(reporter: &Reporter) -> io::Result<()> {
+
+    let mut editor: Editor<Completion, DefaultHistory> = Editor::new().map_err(io::Error::other)?;
+    editor.set_helper(Some(Completion { names: Vec::new() }));
+    let history = history_path();
+    if let Some(ref path) = history {
+        let _ = editor.load_history(path);
+    }
+
+    let mut stdout = io::stdout();
+    let mut session = Session::new();
+    let mut buffer = String::new();
+
+    let result = loop {
+        let prompt = if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                buffer.push_str(&line);
+                buffer.push('\n');
+            },
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue
+            },
+            Err(ReadlineError::Eof) => {
+                if !buffer.trim().is_empty() {
+                    session.execute(&buffer, reporter, &mut stdout)?;
+                }
+                break Ok(())
+            },
+            Err(error) => break Err(io::Error::other(error)),
+        }
+        if !is_complete(&buffer) {
+            continue
+        }
+
+        let _ = editor.add_history_entry(buffer.trim_end());
+        session.execute(&buffer, reporter, &mut stdout)?;
+        buffer.clear();
+        if let Some(completion) = editor.helper_mut() {
+            completion.names = session.env.borrow().bindings().into_iter().map(|(name, _)| name).collect();
+        }
+    };
+
+    if let Some(ref path) = history {
+        let _ = editor.save_history(path);
+    }
+    result
+}
+
+#[test]
+fn session_test() {
+    let reporter = Reporter { json: false, color: false };
+    let mut session = Session::new();
+    assert_eq!(Some(Object::Null), session.eval(SOURCE_NAME, "let add = fn(a, b) { a + b };", &reporter));
+    assert_eq!(None, session.eval(SOURCE_NAME, "let x = 2; x + true;", &reporter));
+    assert_eq!(None, session.eval(SOURCE_NAME, "let = 1;", &reporter));
+    assert_eq!(Some(Object::Int(3)), session.eval(SOURCE_NAME, "add(x, 1)", &reporter));
+}
+
+#[test]
+fn session_locate_test() {
+    let reporter = Reporter { json: false, color: false };
+    let mut session = Session::new();
+    session.eval(SOURCE_NAME, "let f = fn(x) { x + true };", &reporter);
+    session.eval(SOURCE_NAME, "let = 1;", &reporter);
+    session.eval("lib.mk", "let g = fn() {\n  f(1)\n};", &reporter);
+    session.eval(SOURCE_NAME, "g()", &reporter);
+    assert_eq!(3, session.inputs.len());
+
+    let position = |offset, line, column| Position { offset, line, column };
+    let at = |offset| Span::new(position(offset, 1, 1), position(offset, 1, 1));
+    assert_eq!("let f = fn(x) { x + true };", session.locate(at(16)).source);
+    assert_eq!("lib.mk", session.locate(at(28)).file);
+    assert_eq!("lib.mk", session.locate(at(45)).file);
+    assert_eq!("g()", session.locate(at(53)).source);
+
+    let input = session.locate(at(45));
+    assert_eq!(Span::new(position(17, 2, 3), position(18, 2, 4)), rebase(Span::new(position(45, 2, 3), position(46, 2, 4)), input.offset));
+}
+
+#[test]
+fn is_complete_test() {
+    assert!(is_complete("1 + 2"));
+    assert!(is_complete(""));
+    assert!(!is_complete("let f = fn(x) {"));
+    assert!(!is_complete("let f = fn(x) {\n  if (x < 1) { 0 } else {\n"));
+    assert!(is_complete("let f = fn(x) {\n  if (x < 1) { 0 } else { x }\n};"));
+    assert!(!is_complete("add(1,\n"));
+    assert!(!is_complete("[1,\n"));
+    assert!(is_complete("1 }"));
+}
+
+#[cfg(test)]
+fn execute(session: &mut Session, input: &str) -> String {
+    let reporter = Reporter { json: false, color: false };
+    let mut out = Vec::new();
+    session.execute(input, &reporter, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn execute_test() {
+    let mut session = Session::new();
+    assert_eq!("", execute(&mut session, "let b = 2;"));
+    assert_eq!("", execute(&mut session, "let a = 1;"));
+    assert_eq!("3\n", execute(&mut session, "a + b"));
+    assert_eq!("a = 1\nb = 2\n", execute(&mut session, ":env"));
+    assert_eq!("1:1 Identifier(\"a\")\n1:3 Plus\n1:4 Illegal (unexpected character `@`)\n1:5 EndOfFile\n", execute(&mut session, ":tokens a +@"));
+    assert!(execute(&mut session, ":ast -a").starts_with("(-a)\nProgram {"));
+    assert!(execute(&mut session, ":time a * 5").starts_with("5\ntime: "));
+    assert_eq!("", execute(&mut session, ":reset"));
+    assert_eq!("", execute(&mut session, ":env"));
+    assert_eq!("", execute(&mut session, ":nope"));
+}
+
+#[test]
+fn completion_test() {
+    let completion = Completion { names: vec!["length".to_string(), "left".to_string()] };
+    assert_eq!((4, vec!["left".to_string(), "len".to_string(), "length".to_string(), "let".to_string()]), completion.candidates("1 + le", 6));
+    assert_eq!((0, vec!["rest".to_string(), "return".to_string()]), completion.candidates("re", 2));
+    assert_eq!((1, vec!["time".to_string(), "tokens".to_string()]), completion.candidates(":t", 2));
+    assert_eq!((3, Vec::<String>::new()), completion.candidates("1 +", 3));
+}
@@ -1,34 +1,51 @@
 use std::fmt;
+use num_bigint::BigInt;
 use self::Token::*;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub enum Token {
+    #[default]
     Illegal,
     EndOfFile,
 
     // literals
     Identifier(String),
-    Integer(u32),
+    Integer(BigInt),
+    Float(f64),
+    Str(String),
 
     // operators
     Assign,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    PercentAssign,
     Plus,
     Minus,
     Bang,
     Asterisk,
     Slash,
+    Percent,
     LowerThan,
     GreaterThan,
+    LowerThanOrEqual,
+    GreaterThanOrEqual,
     Equal,
     NotEqual,
+    And,
+    Or,
 
     // delimiters
     Comma,
+    Colon,
     Semicolon,
     LeftParenthesis,
     RightParenthesis,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
 
     // keywords
     Function,
@@ -38,33 +55,122 @@ pub enum Token {
     If,
     Else,
     Return,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Illegal => write!(f, "illegal"),
+            EndOfFile => write!(f, "EOF"),
+            Identifier(ref name) => write!(f, "{}", name),
+            Integer(ref value) => write!(f, "{}", value),
+            Float(value) => write!(f, "{:?}", value),
+            Str(ref value) => write!(f, "{:?}", value),
+            Assign => write!(f, "="),
+            PlusAssign => write!(f, "+="),
+            MinusAssign => write!(f, "-="),
+            AsteriskAssign => write!(f, "*="),
+            SlashAssign => write!(f, "/="),
+            PercentAssign => write!(f, "%="),
             Minus => write!(f, "-"),
             Bang => write!(f, "!"),
             Plus => write!(f, "+"),
             Asterisk => write!(f, "*"),
             Slash => write!(f, "/"),
+            Percent => write!(f, "%"),
             LowerThan => write!(f, "<"),
             GreaterThan => write!(f, ">"),
+            LowerThanOrEqual => write!(f, "<="),
+            GreaterThanOrEqual => write!(f, ">="),
             Equal => write!(f, "=="),
             NotEqual => write!(f, "!="),
-            _ => write!(f, "{:?}", self),
+            And => write!(f, "&&"),
+            Or => write!(f, "||"),
+            Comma => write!(f, ","),
+            Colon => write!(f, ":"),
+            Semicolon => write!(f, ";"),
+            LeftParenthesis => write!(f, "("),
+            RightParenthesis => write!(f, ")"),
+            LeftBrace => write!(f, "{{"),
+            RightBrace => write!(f, "}}"),
+            LeftBracket => write!(f, "["),
+            RightBracket => write!(f, "]"),
+            Function => write!(f, "fn"),
+            Let => write!(f, "let"),
+            True => write!(f, "true"),
+            False => write!(f, "false"),
+            If => write!(f, "if"),
+            Else => write!(f, "else"),
+            Return => write!(f, "return"),
+            While => write!(f, "while"),
+            For => write!(f, "for"),
+            In => write!(f, "in"),
+            Break => write!(f, "break"),
+            Continue => write!(f, "continue"),
         }
+    }
+}
+
+/// A location in the source: a byte offset plus the 1-based line and column
+/// (counted in characters) it corresponds to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
 
+impl Default for Position {
+    fn default() -> Position {
+        Position { offset: 0, line: 1, column: 1 }
     }
 }
 
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The half-open source range `start..end` covered by a token or a node.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    /// The span running from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start, end: other.end }
+    }
+}
 
-impl Default for Token {
-    fn default() -> Token {
-        Token::Illegal
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.start)
     }
 }
 
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+/// Every word `lookup_identifier` treats as a keyword.
+pub const KEYWORDS: &[&str] = &["fn", "let", "true", "false", "if", "else", "return", "while", "for", "in", "break", "continue"];
+
 pub fn lookup_identifier(key: &str) -> Token {
     match key {
         "fn" => Token::Function,
@@ -74,6 +180,11 @@ pub fn lookup_identifier(key: &str) -> Token {
         "if" => Token::If,
         "else" => Token::Else,
         "return" => Token::Return,
+        "while" => Token::While,
+        "for" => Token::For,
+        "in" => Token::In,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
         _ => Token::Identifier(key.to_string()),
     }
 }
@@ -90,3 +201,18 @@ fn token_default_test() {
 fn lookup_identifier_test() {
     assert_eq!(lookup_identifier("fn"), Token::Function);
 }
+
+#[test]
+fn span_to_test() {
+    let a = Span::new(Position { offset: 0, line: 1, column: 1 }, Position { offset: 3, line: 1, column: 4 });
+    let b = Span::new(Position { offset: 6, line: 2, column: 2 }, Position { offset: 8, line: 2, column: 4 });
+    assert_eq!(Span::new(a.start, b.end), a.to(b));
+    assert_eq!("1:1", a.to_string());
+}
+
+#[test]
+fn keywords_test() {
+    for keyword in KEYWORDS {
+        assert_ne!(Token::Identifier(keyword.to_string()), lookup_identifier(keyword));
+    }
+}